@@ -0,0 +1,87 @@
+// Tracks which pieces of a torrent we have.
+// 
+// Bits are stored most-significant-bit first, with piece 0 being the high bit of the
+// first byte, which is the same layout the peer wire protocol uses for `bitfield` messages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PieceBitfield {
+	bytes: Vec<u8>,
+	len: usize,
+}
+
+impl PieceBitfield {
+	// Creates a bitfield of `len` pieces, none of which are set.
+	pub fn new(len: usize) -> PieceBitfield {
+		PieceBitfield {
+			bytes: vec![0; len.div_ceil(8)],
+			len,
+		}
+	}
+	
+	pub fn len(&self) -> usize {
+		self.len
+	}
+	
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+	
+	// Returns false for indices past the end of the bitfield.
+	pub fn get(&self, index: usize) -> bool {
+		if index >= self.len {
+			return false;
+		}
+		
+		self.bytes[index / 8] & (0x80 >> (index % 8)) != 0
+	}
+	
+	pub fn set(&mut self, index: usize, value: bool) {
+		assert!(index < self.len, "piece index {} out of range for bitfield of length {}", index, self.len);
+		
+		if value {
+			self.bytes[index / 8] |= 0x80 >> (index % 8);
+		} else {
+			self.bytes[index / 8] &= !(0x80 >> (index % 8));
+		}
+	}
+	
+	// Number of pieces we have.
+	pub fn count_ones(&self) -> usize {
+		self.bytes.iter().map(|b| b.count_ones() as usize).sum()
+	}
+	
+	pub fn is_complete(&self) -> bool {
+		self.count_ones() == self.len
+	}
+	
+	// The raw bytes, as sent in a `bitfield` message. Spare bits at the end are always zero.
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.bytes
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	fn test_set_get() {
+		let mut bitfield = PieceBitfield::new(10);
+		
+		assert_eq!(bitfield.as_bytes().len(), 2);
+		assert!(!bitfield.get(0));
+		
+		bitfield.set(0, true);
+		bitfield.set(9, true);
+		
+		assert!(bitfield.get(0));
+		assert!(bitfield.get(9));
+		assert!(!bitfield.get(10));
+		assert_eq!(bitfield.as_bytes(), &[0x80, 0x40]);
+		assert_eq!(bitfield.count_ones(), 2);
+		
+		bitfield.set(0, false);
+		assert!(!bitfield.get(0));
+		assert!(!bitfield.is_complete());
+	}
+}
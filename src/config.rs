@@ -1,4 +1,304 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use reqwest::{Client, ClientBuilder};
+use reqwest::header::{HeaderName, HeaderValue};
+
+
+// Which IP versions to use when talking to trackers and peers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AddressFamily {
+	Any,
+	V4,
+	V6,
+}
+
+impl AddressFamily {
+	pub fn allows(&self, ip: &IpAddr) -> bool {
+		match self {
+			AddressFamily::Any => true,
+			AddressFamily::V4  => ip.is_ipv4(),
+			AddressFamily::V6  => ip.is_ipv6(),
+		}
+	}
+}
+
+
+// The address trackers should give out to peers for us, in place of the address our announces
+// come from. BEP 3 allows either an IP address or a DNS name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnnounceHost {
+	Ip(IpAddr),
+	Hostname(String),
+}
+
+impl FromStr for AnnounceHost {
+	type Err = String;
+	
+	fn from_str(s: &str) -> Result<AnnounceHost, String> {
+		if let Ok(ip) = s.parse() {
+			return Ok(AnnounceHost::Ip(ip));
+		}
+		
+		// Each dot-separated label must be 1-63 letters, digits, or hyphens, and can't
+		// begin or end with a hyphen.
+		let valid_label = |label: &str| {
+			(1..=63).contains(&label.len())
+				&& label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+				&& !label.starts_with('-')
+				&& !label.ends_with('-')
+		};
+		
+		let hostname = s.strip_suffix('.').unwrap_or(s);
+		
+		if hostname.len() <= 253 && hostname.split('.').all(valid_label) {
+			Ok(AnnounceHost::Hostname(hostname.to_ascii_lowercase()))
+		} else {
+			Err(format!("'{}' is neither an IP address nor a valid hostname", s))
+		}
+	}
+}
+
+impl fmt::Display for AnnounceHost {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			AnnounceHost::Ip(ip)             => write!(f, "{}", ip),
+			AnnounceHost::Hostname(hostname) => write!(f, "{}", hostname),
+		}
+	}
+}
+
+
 pub struct NetworkSettings {
-	pub ip: Option<String>,
+	pub ip: Option<AnnounceHost>,
 	pub port: u64,
+	
+	// The port to announce to trackers, if different from the port we listen on,
+	// such as when behind a NAT that forwards a different external port.
+	pub announce_port: Option<u16>,
+	
+	// Accept invalid (e.g. self-signed or expired) TLS certificates from HTTPS trackers.
+	// Some private trackers need this, but it leaves announces open to interception.
+	pub danger_accept_invalid_certs: bool,
+	
+	// Tell trackers we support (or require) encrypted peer connections.
+	pub support_crypto: bool,
+	pub require_crypto: bool,
+	
+	// Restricts announces, and the peers returned from them, to one IP version.
+	pub address_family: AddressFamily,
+	
+	// Headers sent with every HTTP announce, for private trackers that authenticate with
+	// a header (e.g., `Authorization` or `Cookie`) rather than a passkey in the url.
+	pub extra_headers: Vec<(String, String)>,
+}
+
+impl Default for NetworkSettings {
+	// Listens on the conventional BitTorrent port, with no overrides or crypto.
+	fn default() -> Self {
+		NetworkSettings {
+			ip:                          None,
+			port:                        6881,
+			announce_port:               None,
+			danger_accept_invalid_certs: false,
+			support_crypto:              false,
+			require_crypto:              false,
+			address_family:              AddressFamily::Any,
+			extra_headers:               Vec::new(),
+		}
+	}
+}
+
+impl NetworkSettings {
+	pub fn client_builder(&self) -> ClientBuilder {
+		let builder = Client::builder()
+			.danger_accept_invalid_certs(self.danger_accept_invalid_certs);
+		
+		// Binding to the unspecified address of one family means connections can only be
+		// made over that family.
+		match self.address_family {
+			AddressFamily::Any => builder,
+			AddressFamily::V4  => builder.local_address(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+			AddressFamily::V6  => builder.local_address(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+		}
+	}
+	
+	// Builds the HTTP client to use for announces, configured according to these settings.
+	pub fn build_client(&self) -> Result<Client, reqwest::Error> {
+		self.client_builder().build()
+	}
+}
+
+
+// Fluent builder for `NetworkSettings`, starting from the defaults.
+// Nothing is validated until `build` is called.
+#[derive(Default)]
+pub struct NetworkSettingsBuilder {
+	ip: Option<String>,
+	settings: NetworkSettings,
+}
+
+impl NetworkSettingsBuilder {
+	pub fn new() -> NetworkSettingsBuilder {
+		NetworkSettingsBuilder::default()
+	}
+	
+	// An IP address or hostname, parsed as an `AnnounceHost`.
+	pub fn ip(mut self, ip: &str) -> NetworkSettingsBuilder {
+		self.ip = Some(ip.to_string());
+		self
+	}
+	
+	pub fn port(mut self, port: u64) -> NetworkSettingsBuilder {
+		self.settings.port = port;
+		self
+	}
+	
+	pub fn announce_port(mut self, announce_port: u16) -> NetworkSettingsBuilder {
+		self.settings.announce_port = Some(announce_port);
+		self
+	}
+	
+	pub fn danger_accept_invalid_certs(mut self, accept: bool) -> NetworkSettingsBuilder {
+		self.settings.danger_accept_invalid_certs = accept;
+		self
+	}
+	
+	pub fn support_crypto(mut self, support: bool) -> NetworkSettingsBuilder {
+		self.settings.support_crypto = support;
+		self
+	}
+	
+	pub fn require_crypto(mut self, require: bool) -> NetworkSettingsBuilder {
+		self.settings.require_crypto = require;
+		self
+	}
+	
+	pub fn address_family(mut self, address_family: AddressFamily) -> NetworkSettingsBuilder {
+		self.settings.address_family = address_family;
+		self
+	}
+	
+	pub fn add_header(mut self, name: &str, value: &str) -> NetworkSettingsBuilder {
+		self.settings.extra_headers.push((name.to_string(), value.to_string()));
+		self
+	}
+	
+	pub fn build(self) -> Result<NetworkSettings, String> {
+		let mut settings = self.settings;
+		
+		if settings.port == 0 || settings.port > u64::from(u16::MAX) {
+			return Err(format!("invalid port {}", settings.port));
+		}
+		
+		if settings.announce_port == Some(0) {
+			return Err(String::from("invalid announce port 0"));
+		}
+		
+		// Requiring encryption implies supporting it, and trackers expect both keys.
+		if settings.require_crypto && !settings.support_crypto {
+			return Err(String::from("require_crypto is set without support_crypto"));
+		}
+		
+		for (name, value) in &settings.extra_headers {
+			HeaderName::from_bytes(name.as_bytes())
+				.map_err(|_| format!("invalid header name '{}'", name))?;
+			HeaderValue::from_str(value)
+				.map_err(|_| format!("invalid value for header '{}'", name))?;
+		}
+		
+		settings.ip = self.ip
+			.map(|ip| ip.parse())
+			.transpose()?;
+		
+		Ok(settings)
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	fn test_danger_accept_invalid_certs() {
+		let mut ns = NetworkSettings::default();
+		
+		assert!(!format!("{:?}", ns.client_builder()).contains("danger_accept_invalid_certs"));
+		assert!(ns.build_client().is_ok());
+		
+		ns.danger_accept_invalid_certs = true;
+		
+		assert!(format!("{:?}", ns.client_builder()).contains("danger_accept_invalid_certs: true"));
+		assert!(ns.build_client().is_ok());
+	}
+	
+	#[test]
+	fn test_default() {
+		let ns = NetworkSettings::default();
+		
+		assert_eq!(ns.port, 6881);
+		assert_eq!(ns.ip, None);
+		assert_eq!(ns.announce_port, None);
+		assert!(!ns.danger_accept_invalid_certs);
+		assert!(!ns.support_crypto && !ns.require_crypto);
+		assert_eq!(ns.address_family, AddressFamily::Any);
+		assert!(ns.extra_headers.is_empty());
+	}
+	
+	#[test]
+	fn test_builder() {
+		let ns = NetworkSettingsBuilder::new()
+			.ip("peer.example.com")
+			.port(6000)
+			.announce_port(51413)
+			.danger_accept_invalid_certs(true)
+			.support_crypto(true)
+			.require_crypto(true)
+			.address_family(AddressFamily::V4)
+			.add_header("Authorization", "Bearer abc")
+			.build()
+			.unwrap();
+		
+		assert_eq!(ns.ip, Some(AnnounceHost::Hostname(String::from("peer.example.com"))));
+		assert_eq!(ns.port, 6000);
+		assert_eq!(ns.announce_port, Some(51413));
+		assert!(ns.danger_accept_invalid_certs);
+		assert!(ns.support_crypto && ns.require_crypto);
+		assert_eq!(ns.address_family, AddressFamily::V4);
+		assert_eq!(ns.extra_headers, vec![(String::from("Authorization"), String::from("Bearer abc"))]);
+		
+		assert!(NetworkSettingsBuilder::new().build().is_ok());
+		assert!(NetworkSettingsBuilder::new().port(0).build().is_err());
+		assert!(NetworkSettingsBuilder::new().port(65536).build().is_err());
+		assert!(NetworkSettingsBuilder::new().announce_port(0).build().is_err());
+		assert!(NetworkSettingsBuilder::new().require_crypto(true).build().is_err());
+		assert!(NetworkSettingsBuilder::new().ip("not a host").build().is_err());
+		assert!(NetworkSettingsBuilder::new().add_header("Bad Header", "x").build().is_err());
+		assert!(NetworkSettingsBuilder::new().add_header("X-Ok", "line\nbreak").build().is_err());
+	}
+	
+	#[test]
+	fn test_announce_host() {
+		assert_eq!("10.0.0.1".parse(), Ok(AnnounceHost::Ip("10.0.0.1".parse().unwrap())));
+		assert_eq!("::1".parse(), Ok(AnnounceHost::Ip("::1".parse().unwrap())));
+		assert_eq!("Peer-1.Example.com.".parse(), Ok(AnnounceHost::Hostname(String::from("peer-1.example.com"))));
+		
+		assert!("".parse::<AnnounceHost>().is_err());
+		assert!("peer..example.com".parse::<AnnounceHost>().is_err());
+		assert!("-peer.example.com".parse::<AnnounceHost>().is_err());
+		assert!("peer.example.com&port=1".parse::<AnnounceHost>().is_err());
+		assert!("peer example".parse::<AnnounceHost>().is_err());
+	}
+	
+	#[test]
+	fn test_address_family_allows() {
+		let v4: IpAddr = "127.0.0.1".parse().unwrap();
+		let v6: IpAddr = "::1".parse().unwrap();
+		
+		assert!(AddressFamily::Any.allows(&v4) && AddressFamily::Any.allows(&v6));
+		assert!(AddressFamily::V4.allows(&v4) && !AddressFamily::V4.allows(&v6));
+		assert!(!AddressFamily::V6.allows(&v4) && AddressFamily::V6.allows(&v6));
+	}
 }
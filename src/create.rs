@@ -0,0 +1,247 @@
+use std::convert::TryFrom;
+use std::path::Path;
+
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::hash::Sha1Hasher;
+use crate::metainfo::{BMetainfo, BMetainfoBuilder};
+
+
+// Creates a torrent of the file or directory at `path`, named after it.
+// 
+// The files of a directory are sorted by path, component by component (comparing bytes, so
+// `B` sorts before `a`), as mainline does. The order of `files` is part of the info hash, and
+// pieces run across files in that order, so this makes the torrent independent of the order
+// the filesystem lists files in. Torrents that are parsed, rather than created, keep the order
+// they were created with. Empty directories are skipped.
+pub async fn create_from_path(path: &Path, announce: &str, piece_length: u64) -> Result<BMetainfo, String> {
+	let name = path.file_name()
+		.and_then(|n| n.to_str())
+		.ok_or_else(|| format!("'{}' has no valid UTF-8 name", path.display()))?;
+	
+	let piece_size = usize::try_from(piece_length).map_err(|e| e.to_string())?;
+	let mut hasher = PieceHasher::new(piece_size)?;
+	
+	let builder = BMetainfoBuilder::new()
+		.announce(announce)
+		.name(name)
+		.piece_length(piece_length);
+	
+	let metadata = tokio::fs::metadata(path).await.map_err(|e| e.to_string())?;
+	
+	let builder = if metadata.is_dir() {
+		let mut files = Vec::new();
+		list_files(path, Vec::new(), &mut files).await?;
+		files.sort();
+		
+		let mut builder = builder;
+		
+		for (components, length) in files {
+			let file = File::open(components.iter().fold(path.to_path_buf(), |p, c| p.join(c))).await
+				.map_err(|e| e.to_string())?;
+			hasher.read_from(file).await?;
+			
+			builder = builder.add_file(length, components);
+		}
+		
+		builder
+	} else {
+		let file = File::open(path).await.map_err(|e| e.to_string())?;
+		hasher.read_from(file).await?;
+		
+		builder.length(metadata.len())
+	};
+	
+	builder.pieces(hasher.finish())
+		.build()
+		.map_err(|e| e.to_string())
+}
+
+// Recursively lists the files under `dir`, as path components relative to the torrent's root.
+async fn list_files(dir: &Path, prefix: Vec<String>, files: &mut Vec<(Vec<String>, u64)>) -> Result<(), String> {
+	let mut entries = tokio::fs::read_dir(dir).await.map_err(|e| e.to_string())?;
+	
+	while let Some(entry) = entries.next_entry().await.map_err(|e| e.to_string())? {
+		let name = entry.file_name().into_string()
+			.map_err(|n| format!("'{}' is not valid UTF-8", n.to_string_lossy()))?;
+		
+		let mut components = prefix.clone();
+		components.push(name);
+		
+		let metadata = tokio::fs::metadata(entry.path()).await.map_err(|e| e.to_string())?;
+		
+		if metadata.is_dir() {
+			Box::pin(list_files(&entry.path(), components, files)).await?;
+		} else {
+			files.push((components, metadata.len()));
+		}
+	}
+	
+	Ok(())
+}
+
+
+// Hashes content for a new torrent as it's read, without buffering more than a piece at a time.
+// Returns the concatenated 20-byte SHA-1 hash of each piece, i.e., the `pieces` field.
+// The final piece is whatever is left over once the reader hits EOF.
+pub async fn hash_pieces_from<R: AsyncRead + Unpin>(reader: R, piece_size: usize) -> Result<Vec<u8>, String> {
+	let mut hasher = PieceHasher::new(piece_size)?;
+	hasher.read_from(reader).await?;
+	
+	Ok(hasher.finish())
+}
+
+// Hashes pieces of content read from any number of readers in turn, so that pieces can span
+// the files of a multi-file torrent. Pieces are hashed as they're read, in chunks of at most
+// `READ_CHUNK_SIZE`, so large pieces are never held in memory whole.
+struct PieceHasher {
+	pieces: Vec<u8>,
+	hasher: Sha1Hasher,
+	piece_size: usize,
+	filled: usize,
+	chunk: Vec<u8>,
+}
+
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+impl PieceHasher {
+	fn new(piece_size: usize) -> Result<PieceHasher, String> {
+		if piece_size == 0 {
+			return Err(String::from("piece size is zero"));
+		}
+		
+		Ok(PieceHasher {
+			pieces: Vec::new(),
+			hasher: Sha1Hasher::new(),
+			piece_size,
+			filled: 0,
+			chunk: vec![0; piece_size.min(READ_CHUNK_SIZE)],
+		})
+	}
+	
+	async fn read_from<R: AsyncRead + Unpin>(&mut self, mut reader: R) -> Result<(), String> {
+		// Reads may return any amount of data, so a piece can span several reads.
+		loop {
+			let want = self.chunk.len().min(self.piece_size - self.filled);
+			let read = reader.read(&mut self.chunk[..want]).await
+				.map_err(|e| e.to_string())?;
+			
+			if read == 0 {
+				return Ok(());
+			}
+			
+			self.hasher.update(&self.chunk[..read]);
+			self.filled += read;
+			
+			if self.filled == self.piece_size {
+				let hasher = std::mem::take(&mut self.hasher);
+				self.pieces.extend_from_slice(&hasher.finish());
+				self.filled = 0;
+			}
+		}
+	}
+	
+	fn finish(mut self) -> Vec<u8> {
+		if self.filled > 0 {
+			self.pieces.extend_from_slice(&self.hasher.finish());
+		}
+		
+		self.pieces
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	use std::io::Cursor;
+	
+	use tokio::io::AsyncWriteExt;
+	
+	const HELLO_WORLD_PIECES: [&str; 3] = [
+		"aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d", // "hello"
+		"83c98a35bd56899544bf192db0ce1c59e6fa16f5", // " worl"
+		"3c363836cf4e16666669a25da280a1865c2d2874", // "d"
+	];
+	
+	fn to_hex(bytes: &[u8]) -> String {
+		bytes.iter().map(|b| format!("{:02x}", b)).collect()
+	}
+	
+	#[tokio::test]
+	async fn test_hash_pieces_from() {
+		let pieces = hash_pieces_from(Cursor::new(b"hello world"), 5).await.unwrap();
+		
+		assert_eq!(pieces.len(), 3 * 20);
+		assert_eq!(to_hex(&pieces), HELLO_WORLD_PIECES.concat());
+		
+		// Nothing to hash.
+		assert!(hash_pieces_from(Cursor::new(b""), 5).await.unwrap().is_empty());
+		assert!(hash_pieces_from(Cursor::new(b"hello"), 0).await.is_err());
+	}
+	
+	#[tokio::test]
+	async fn test_hash_pieces_from_short_reads() {
+		// The duplex stream hands over at most 3 bytes per read, so pieces span several reads.
+		let (mut writer, reader) = tokio::io::duplex(3);
+		
+		let write = tokio::spawn(async move {
+			writer.write_all(b"hello world").await.unwrap();
+		});
+		
+		let pieces = hash_pieces_from(reader, 5).await.unwrap();
+		write.await.unwrap();
+		
+		assert_eq!(to_hex(&pieces), HELLO_WORLD_PIECES.concat());
+	}
+	
+	#[tokio::test]
+	async fn test_create_from_path_sorts_files() {
+		let root = std::env::temp_dir().join(format!("acorntorrent-create-{}", std::process::id()));
+		let dir  = root.join("content");
+		std::fs::create_dir_all(dir.join("a")).unwrap();
+		std::fs::create_dir_all(dir.join("empty")).unwrap();
+		
+		// Written out of order, and with an uppercase name, which sorts before lowercase.
+		std::fs::write(dir.join("b.txt"), b"world").unwrap();
+		std::fs::write(dir.join("a").join("z.txt"), b" ").unwrap();
+		std::fs::write(dir.join("C.txt"), b"hel").unwrap();
+		std::fs::write(dir.join("a").join("c.txt"), b"lo").unwrap();
+		
+		let metainfo = create_from_path(&dir, "http://tracker.example.com/announce", 5).await.unwrap();
+		let again    = create_from_path(&dir, "http://tracker.example.com/announce", 5).await.unwrap();
+		std::fs::remove_dir_all(&root).unwrap();
+		
+		let paths: Vec<String> = metainfo.info.files.as_ref().unwrap().iter()
+			.map(|f| f.path.join("/"))
+			.collect();
+		assert_eq!(paths, vec!["C.txt", "a/c.txt", "a/z.txt", "b.txt"]);
+		
+		assert_eq!(metainfo.info.name, "content");
+		assert_eq!(to_hex(&metainfo.info.pieces), HELLO_WORLD_PIECES.concat());
+		assert!(metainfo.validate().is_ok());
+		assert_eq!(metainfo.info_hash().unwrap(), again.info_hash().unwrap());
+		
+		// Parsed torrents keep their original, here unsorted, order.
+		let parsed = BMetainfo::from_path("test_torrents/multi_file_md5sum.torrent").unwrap();
+		let paths: Vec<String> = parsed.info.files.as_ref().unwrap().iter()
+			.map(|f| f.path.join("/"))
+			.collect();
+		assert_eq!(paths, vec!["readme.txt", "data/data.bin"]);
+	}
+	
+	#[tokio::test]
+	async fn test_create_from_path_single_file() {
+		let path = std::env::temp_dir().join(format!("acorntorrent-create-{}.txt", std::process::id()));
+		std::fs::write(&path, b"hello world").unwrap();
+		
+		let metainfo = create_from_path(&path, "http://tracker.example.com/announce", 5).await.unwrap();
+		std::fs::remove_file(&path).unwrap();
+		
+		assert_eq!(metainfo.info.length, Some(11));
+		assert!(metainfo.info.files.is_none());
+		assert_eq!(to_hex(&metainfo.info.pieces), HELLO_WORLD_PIECES.concat());
+	}
+}
@@ -0,0 +1,250 @@
+// Human-readable formatting of byte counts, in the style of `transmission-show`.
+
+const SI_UNITS:  [&str; 5] = ["kB",  "MB",  "GB",  "TB",  "PB"];
+const IEC_UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+
+// How to round the last displayed decimal place when a value lies exactly halfway.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoundingMode {
+	// Round halves up, e.g., 1.005 -> 1.01.
+	HalfUp,
+	
+	// Round halves to the nearest even digit, e.g., 1.005 -> 1.00, but 1.015 -> 1.02.
+	HalfEven,
+}
+
+
+// Formats a byte count using SI (powers of 1000) units. Whole numbers are printed
+// without a fractional part, otherwise two decimal places are shown.
+pub fn fuzzy_format_bytes_to_si(bytes: u64) -> String {
+	fuzzy_format_bytes_to_si_with_rounding(bytes, RoundingMode::HalfEven)
+}
+
+// As `fuzzy_format_bytes_to_si`, but with explicit control over rounding, so the output can
+// be made to match a particular tool. The arithmetic is done on integers, so values that lie
+// exactly halfway are always detected, unlike with floats.
+pub fn fuzzy_format_bytes_to_si_with_rounding(bytes: u64, rounding: RoundingMode) -> String {
+	format_bytes(bytes, 1000, &SI_UNITS, rounding)
+}
+
+// Formats a byte count using IEC (powers of 1024) units, e.g., `16 KiB`, in the same way
+// as `fuzzy_format_bytes_to_si`.
+pub fn fuzzy_format_bytes_to_iec(bytes: u64) -> String {
+	format_bytes(bytes, 1024, &IEC_UNITS, RoundingMode::HalfEven)
+}
+
+fn format_bytes(bytes: u64, base: u128, units: &[&str], rounding: RoundingMode) -> String {
+	if (bytes as u128) < base {
+		return format!("{} B", bytes);
+	}
+	
+	let mut unit    = 0;
+	let mut divisor = base;
+	
+	while bytes as u128 >= divisor * base && unit < units.len() - 1 {
+		divisor *= base;
+		unit += 1;
+	}
+	
+	if (bytes as u128).is_multiple_of(divisor) {
+		return format!("{} {}", bytes as u128 / divisor, units[unit]);
+	}
+	
+	// The value in hundredths of a unit, and the remainder for rounding.
+	let scaled         = bytes as u128 * 100;
+	let mut hundredths = scaled / divisor;
+	let remainder      = scaled % divisor;
+	
+	let round_up = match rounding {
+		RoundingMode::HalfUp   => remainder * 2 >= divisor,
+		RoundingMode::HalfEven => remainder * 2 > divisor || (remainder * 2 == divisor && hundredths % 2 == 1),
+	};
+	
+	if round_up {
+		hundredths += 1;
+	}
+	
+	// Rounding up can carry into a whole unit more, e.g., 999.995 kB -> 1000.00 kB,
+	// which is shown as the next unit instead, i.e., 1.00 MB.
+	if hundredths >= base * 100 && unit < units.len() - 1 {
+		hundredths /= base;
+		unit += 1;
+	}
+	
+	format!("{}.{:02} {}", hundredths / 100, hundredths % 100, units[unit])
+}
+
+// Parses a size such as `13 B`, `1.5 MB`, or `16 KiB` into a byte count.
+// Both SI (`kB`, `MB`, ...) and binary (`KiB`, `MiB`, ...) units are accepted.
+pub fn parse_size_to_bytes(s: &str) -> Option<u64> {
+	parse_size_to_bytes_with_convention(s, false)
+}
+
+// Like `parse_size_to_bytes`, but with `binary_kb`, `KB`, `MB`, ... are powers of 1024, as some
+// tools (e.g., older versions of Transmission) use them. `kB` and the `KiB` family are unambiguous,
+// and so are unaffected.
+pub fn parse_size_to_bytes_with_convention(s: &str, binary_kb: bool) -> Option<u64> {
+	let s = s.trim();
+	let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.'))?;
+	let (val, unit) = s.split_at(split);
+	
+	let base: u64 = if binary_kb { 1024 } else { 1000 };
+	
+	let val: f64 = val.parse().ok()?;
+	let multiplier: u64 = match unit.trim() {
+		"B" | "bytes"   => 1,
+		"kB"            => 1000,
+		"KB"            => base,
+		"MB"            => base.pow(2),
+		"GB"            => base.pow(3),
+		"TB"            => base.pow(4),
+		"PB"            => base.pow(5),
+		"KiB"           => 1 << 10,
+		"MiB"           => 1 << 20,
+		"GiB"           => 1 << 30,
+		"TiB"           => 1 << 40,
+		"PiB"           => 1 << 50,
+		_ => return None,
+	};
+	
+	Some((val * multiplier as f64).round() as u64)
+}
+
+// Compares two formatted sizes, allowing them to differ by up to `tolerance_ratio` of the
+// larger. Different tools round formatted sizes differently, so exact comparisons are brittle.
+pub fn sizes_approximately_equal(a: &str, b: &str, tolerance_ratio: f64) -> bool {
+	match (parse_size_to_bytes(a), parse_size_to_bytes(b)) {
+		(Some(a), Some(b)) => {
+			let diff    = a.abs_diff(b) as f64;
+			let largest = a.max(b) as f64;
+			
+			diff <= largest * tolerance_ratio
+		}
+		_ => false,
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	fn test_fuzzy_format_bytes_to_si() {
+		assert_eq!(fuzzy_format_bytes_to_si(13), "13 B");
+		assert_eq!(fuzzy_format_bytes_to_si(16000), "16 kB");
+		assert_eq!(fuzzy_format_bytes_to_si(16384), "16.38 kB");
+		assert_eq!(fuzzy_format_bytes_to_si(1_500_000), "1.50 MB");
+		assert_eq!(fuzzy_format_bytes_to_si(2_000_000_000), "2 GB");
+	}
+	
+	#[test]
+	fn test_unit_rollover() {
+		assert_eq!(fuzzy_format_bytes_to_si(999_499), "999.50 kB");
+		assert_eq!(fuzzy_format_bytes_to_si(999_500), "999.50 kB");
+		assert_eq!(fuzzy_format_bytes_to_si(999_949), "999.95 kB");
+		assert_eq!(fuzzy_format_bytes_to_si(999_950), "999.95 kB");
+		assert_eq!(fuzzy_format_bytes_to_si(999_994), "999.99 kB");
+		
+		// These round up to 1000.00 kB, so are promoted.
+		assert_eq!(fuzzy_format_bytes_to_si(999_995), "1.00 MB");
+		assert_eq!(fuzzy_format_bytes_to_si(999_999), "1.00 MB");
+		assert_eq!(fuzzy_format_bytes_to_si_with_rounding(999_995_000, RoundingMode::HalfUp), "1.00 GB");
+		assert_eq!(fuzzy_format_bytes_to_iec((1 << 20) - 1), "1.00 MiB");
+		
+		// There's no unit to promote to beyond the largest.
+		assert_eq!(fuzzy_format_bytes_to_si(999_999_999_999_999_999), "1000.00 PB");
+	}
+	
+	#[test]
+	fn test_fuzzy_format_bytes_to_iec() {
+		assert_eq!(fuzzy_format_bytes_to_iec(1000), "1000 B");
+		assert_eq!(fuzzy_format_bytes_to_iec(16384), "16 KiB");
+		assert_eq!(fuzzy_format_bytes_to_iec(16000), "15.62 KiB");
+		assert_eq!(fuzzy_format_bytes_to_iec(3 << 30), "3 GiB");
+	}
+	
+	// Formatting then parsing should give back the original size, give or take the rounding
+	// to two decimal places of the unit it was formatted in.
+	fn assert_round_trips(formatted: &str, bytes: u64) -> Result<(), proptest::test_runner::TestCaseError> {
+		let unit  = formatted.split(' ').nth(1).unwrap();
+		let scale = parse_size_to_bytes(&format!("1 {}", unit)).unwrap();
+		
+		let parsed    = parse_size_to_bytes(formatted).unwrap();
+		let tolerance = if scale == 1 { 0 } else { scale / 200 + 1 };
+		
+		proptest::prop_assert!(
+			parsed.abs_diff(bytes) <= tolerance,
+			"{} formatted as '{}', which parses as {}", bytes, formatted, parsed,
+		);
+		
+		Ok(())
+	}
+	
+	proptest::proptest! {
+		#[test]
+		fn test_si_round_trip(bytes in 0..u64::MAX / 2) {
+			assert_round_trips(&fuzzy_format_bytes_to_si(bytes), bytes)?;
+		}
+		
+		#[test]
+		fn test_iec_round_trip(bytes in 0..u64::MAX / 2) {
+			assert_round_trips(&fuzzy_format_bytes_to_iec(bytes), bytes)?;
+		}
+		
+		#[test]
+		fn test_small_round_trip(bytes in 0..10_000_000u64) {
+			assert_round_trips(&fuzzy_format_bytes_to_si(bytes), bytes)?;
+			assert_round_trips(&fuzzy_format_bytes_to_iec(bytes), bytes)?;
+		}
+	}
+	
+	#[test]
+	fn test_rounding_mode() {
+		// Exactly halfway, with an even last digit.
+		assert_eq!(fuzzy_format_bytes_to_si_with_rounding(102_345_000, RoundingMode::HalfUp),   "102.35 MB");
+		assert_eq!(fuzzy_format_bytes_to_si_with_rounding(102_345_000, RoundingMode::HalfEven), "102.34 MB");
+		
+		// Exactly halfway, with an odd last digit.
+		assert_eq!(fuzzy_format_bytes_to_si_with_rounding(102_355_000, RoundingMode::HalfUp),   "102.36 MB");
+		assert_eq!(fuzzy_format_bytes_to_si_with_rounding(102_355_000, RoundingMode::HalfEven), "102.36 MB");
+		
+		// Either side of halfway rounds the same regardless of mode.
+		assert_eq!(fuzzy_format_bytes_to_si_with_rounding(102_344_999, RoundingMode::HalfUp),   "102.34 MB");
+		assert_eq!(fuzzy_format_bytes_to_si_with_rounding(102_345_001, RoundingMode::HalfEven), "102.35 MB");
+	}
+	
+	#[test]
+	fn test_parse_size_to_bytes() {
+		assert_eq!(parse_size_to_bytes("13 B"), Some(13));
+		assert_eq!(parse_size_to_bytes("16.38 kB"), Some(16380));
+		assert_eq!(parse_size_to_bytes("16 KiB"), Some(16384));
+		assert_eq!(parse_size_to_bytes("1.5 MB"), Some(1_500_000));
+		assert_eq!(parse_size_to_bytes("1.5"), None);
+		assert_eq!(parse_size_to_bytes("MB"), None);
+		assert_eq!(parse_size_to_bytes("1.5 XB"), None);
+	}
+	
+	#[test]
+	fn test_parse_size_to_bytes_with_convention() {
+		assert_eq!(parse_size_to_bytes_with_convention("4 KB", false), Some(4000));
+		assert_eq!(parse_size_to_bytes_with_convention("4 KB", true), Some(4096));
+		assert_eq!(parse_size_to_bytes_with_convention("1.5 MB", true), Some(1_572_864));
+		
+		// Unambiguous units don't depend on the convention.
+		assert_eq!(parse_size_to_bytes_with_convention("4 kB", true), Some(4000));
+		assert_eq!(parse_size_to_bytes_with_convention("4 KiB", false), Some(4096));
+	}
+	
+	#[test]
+	fn test_sizes_approximately_equal() {
+		assert!(sizes_approximately_equal("1023.95 MB", "1023.96 MB", 0.0001));
+		assert!(sizes_approximately_equal("16.38 kB", "16 KiB", 0.001));
+		assert!(sizes_approximately_equal("1 GB", "1 GB", 0.0));
+		
+		assert!(!sizes_approximately_equal("1.00 MB", "1.10 MB", 0.01));
+		assert!(!sizes_approximately_equal("1 MB", "1 GB", 0.5));
+		assert!(!sizes_approximately_equal("1 MB", "garbage", 1.0));
+	}
+}
@@ -0,0 +1,150 @@
+// SHA-1, as used for v1 info hashes and piece hashes, and SHA-256.
+// 
+// These are backed by `ring` by default. For targets `ring` doesn't support, disable default
+// features and enable `pure-rust-sha1` to use the pure-Rust `sha1` and `sha2` crates instead.
+// If both are enabled, `ring` is used. Either way, the output is identical.
+
+#[cfg(not(any(feature = "ring", feature = "pure-rust-sha1")))]
+compile_error!("either the `ring` or `pure-rust-sha1` feature must be enabled");
+
+
+pub fn sha1(bytes: &[u8]) -> [u8; 20] {
+	#[cfg(feature = "ring")]
+	return sha1_ring(bytes);
+	
+	#[cfg(not(feature = "ring"))]
+	return sha1_pure_rust(bytes);
+}
+
+#[cfg(feature = "ring")]
+fn sha1_ring(bytes: &[u8]) -> [u8; 20] {
+	let digest = ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, bytes);
+	
+	let mut hash = [0; 20];
+	hash.copy_from_slice(digest.as_ref());
+	hash
+}
+
+// When `ring` is also enabled, this is only used to check the two backends agree.
+#[cfg(feature = "pure-rust-sha1")]
+#[cfg_attr(feature = "ring", allow(dead_code))]
+fn sha1_pure_rust(bytes: &[u8]) -> [u8; 20] {
+	use sha1_crate::{Digest, Sha1};
+	
+	Sha1::digest(bytes).into()
+}
+
+// SHA-1 of data fed in a piece at a time, so that large pieces (often 16 MiB) can be hashed
+// as they're read, without holding the whole piece in memory.
+pub struct Sha1Hasher {
+	#[cfg(feature = "ring")]
+	context: ring::digest::Context,
+	
+	#[cfg(not(feature = "ring"))]
+	hasher: sha1_crate::Sha1,
+}
+
+impl Sha1Hasher {
+	pub fn new() -> Sha1Hasher {
+		Sha1Hasher {
+			#[cfg(feature = "ring")]
+			context: ring::digest::Context::new(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY),
+			
+			#[cfg(not(feature = "ring"))]
+			hasher: sha1_crate::Digest::new(),
+		}
+	}
+	
+	pub fn update(&mut self, bytes: &[u8]) {
+		#[cfg(feature = "ring")]
+		self.context.update(bytes);
+		
+		#[cfg(not(feature = "ring"))]
+		sha1_crate::Digest::update(&mut self.hasher, bytes);
+	}
+	
+	pub fn finish(self) -> [u8; 20] {
+		#[cfg(feature = "ring")]
+		{
+			let mut hash = [0; 20];
+			hash.copy_from_slice(self.context.finish().as_ref());
+			hash
+		}
+		
+		#[cfg(not(feature = "ring"))]
+		sha1_crate::Digest::finalize(self.hasher).into()
+	}
+}
+
+impl Default for Sha1Hasher {
+	fn default() -> Self {
+		Sha1Hasher::new()
+	}
+}
+
+pub fn sha256(bytes: &[u8]) -> [u8; 32] {
+	#[cfg(feature = "ring")]
+	return sha256_ring(bytes);
+	
+	#[cfg(not(feature = "ring"))]
+	return sha256_pure_rust(bytes);
+}
+
+#[cfg(feature = "ring")]
+fn sha256_ring(bytes: &[u8]) -> [u8; 32] {
+	let digest = ring::digest::digest(&ring::digest::SHA256, bytes);
+	
+	let mut hash = [0; 32];
+	hash.copy_from_slice(digest.as_ref());
+	hash
+}
+
+#[cfg(feature = "pure-rust-sha1")]
+#[cfg_attr(feature = "ring", allow(dead_code))]
+fn sha256_pure_rust(bytes: &[u8]) -> [u8; 32] {
+	use sha2::{Digest, Sha256};
+	
+	Sha256::digest(bytes).into()
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	fn test_sha1() {
+		let hash: String = sha1(b"hello").iter().map(|b| format!("{:02x}", b)).collect();
+		assert_eq!(hash, "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d");
+	}
+	
+	#[test]
+	fn test_sha1_hasher() {
+		let data: Vec<u8> = (0..100_000u32).map(|i| (i * 7 + 3) as u8).collect();
+		
+		for chunk_size in [1, 7, 4096, data.len()] {
+			let mut hasher = Sha1Hasher::new();
+			data.chunks(chunk_size).for_each(|c| hasher.update(c));
+			assert_eq!(hasher.finish(), sha1(&data), "chunk size {}", chunk_size);
+		}
+		
+		assert_eq!(Sha1Hasher::new().finish(), sha1(b""));
+	}
+	
+	#[test]
+	fn test_sha256() {
+		let hash: String = sha256(b"hello").iter().map(|b| format!("{:02x}", b)).collect();
+		assert_eq!(hash, "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824");
+	}
+	
+	#[cfg(all(feature = "ring", feature = "pure-rust-sha1"))]
+	#[test]
+	fn test_backends_agree() {
+		let torrent = std::fs::read("test_torrents/hybrid_single_file.torrent").unwrap();
+		
+		for input in [&b""[..], b"hello", &torrent[..], &vec![0xab; 1 << 20][..]] {
+			assert_eq!(sha1_ring(input), sha1_pure_rust(input));
+			assert_eq!(sha256_ring(input), sha256_pure_rust(input));
+		}
+	}
+}
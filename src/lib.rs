@@ -1,9 +1,52 @@
-#![feature(array_map)]
+//! A BitTorrent client library.
+//! 
+//! The primary types are re-exported at the crate root:
+//! 
+//! ```
+//! use acorntorrent::BMetainfo;
+//! 
+//! let metainfo = BMetainfo::from_path("test_torrents/single_file.torrent").unwrap();
+//! 
+//! assert_eq!(metainfo.info_hash().unwrap().len(), 20);
+//! ```
+//! 
+//! Everything that talks to the network or uses tokio is behind the opt-in `net` feature, which
+//! the binary requires. Without it, the parsing and formatting core has no I/O dependencies, and
+//! so builds for `wasm32-unknown-unknown` (with `pure-rust-sha1` in place of `ring`):
+//! 
+//! ```text
+//! cargo build --lib --target wasm32-unknown-unknown --no-default-features --features pure-rust-sha1
+//! ```
+
+// tokio and reqwest's native networking don't build for the browser.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown", feature = "net"))]
+compile_error!("the `net` feature is unsupported on wasm32-unknown-unknown");
 
 pub mod metainfo;
+pub mod bitfield;
+pub mod magnet;
+pub mod format;
+pub mod hash;
+pub mod peer_id;
+
+#[cfg(feature = "net")]
 pub mod torrent;
+#[cfg(feature = "net")]
 pub mod tracker;
+#[cfg(feature = "net")]
 pub mod config;
+#[cfg(feature = "net")]
+pub mod verify;
+#[cfg(feature = "net")]
+pub mod create;
+
+pub use metainfo::{BMetainfo, BInfo};
+#[cfg(feature = "net")]
+pub use torrent::{BTorrent, NumwantStrategy, AnnounceParams};
+#[cfg(feature = "net")]
+pub use tracker::{BTrackerResponse, BAnnounceEvent};
+#[cfg(feature = "net")]
+pub use config::{NetworkSettings, NetworkSettingsBuilder};
 
 
 #[cfg(test)]
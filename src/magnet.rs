@@ -0,0 +1,147 @@
+use percent_encoding::percent_decode_str;
+
+
+// A parsed `magnet:` URI. Only BitTorrent v1 (`urn:btih:`) magnets are supported.
+#[derive(Debug)]
+pub struct MagnetLink {
+	pub info_hash: Vec<u8>,
+	
+	// Suggested name to display until the metadata is fetched (`dn`).
+	pub display_name: Option<String>,
+	
+	// Tracker urls (`tr`).
+	pub trackers: Vec<String>,
+	
+	// Web seed urls (`ws`).
+	pub web_seeds: Vec<String>,
+	
+	// Urls to the .torrent file itself (`xs`).
+	pub exact_sources: Vec<String>,
+}
+
+impl MagnetLink {
+	pub fn parse(uri: &str) -> Result<MagnetLink, String> {
+		let query = uri.strip_prefix("magnet:?")
+			.ok_or_else(|| String::from("not a magnet link"))?;
+		
+		let mut info_hash     = None;
+		let mut display_name  = None;
+		let mut trackers      = Vec::new();
+		let mut web_seeds     = Vec::new();
+		let mut exact_sources = Vec::new();
+		
+		for param in query.split('&').filter(|p| !p.is_empty()) {
+			let (key, val) = param.split_once('=')
+				.ok_or_else(|| format!("magnet link parameter '{}' has no value", param))?;
+			
+			let val = percent_decode_str(&val.replace('+', " "))
+				.decode_utf8()
+				.map_err(|_| format!("magnet link parameter '{}' is not valid UTF-8", key))?
+				.into_owned();
+			
+			match key {
+				"xt" => {
+					// Magnets may carry several exact topics (e.g., a v2 `urn:btmh:` hash too),
+					// so skip any we don't understand.
+					if let Some(hash) = val.strip_prefix("urn:btih:") {
+						info_hash = Some(decode_btih(hash)?);
+					}
+				}
+				"dn" => display_name = Some(val),
+				"tr" => trackers.push(val),
+				"ws" => web_seeds.push(val),
+				"xs" => exact_sources.push(val),
+				_ => {}
+			}
+		}
+		
+		let info_hash = info_hash
+			.ok_or_else(|| String::from("magnet link has no `urn:btih:` info hash"))?;
+		
+		Ok(MagnetLink {
+			info_hash,
+			display_name,
+			trackers,
+			web_seeds,
+			exact_sources,
+		})
+	}
+}
+
+// The info hash is either 40 hex characters, or 32 base32 characters.
+fn decode_btih(hash: &str) -> Result<Vec<u8>, String> {
+	let decoded = match hash.len() {
+		40 => decode_hex(hash),
+		32 => decode_base32(hash),
+		_  => None,
+	};
+	
+	decoded.ok_or_else(|| format!("invalid info hash '{}'", hash))
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+	(0..s.len())
+		.step_by(2)
+		.map(|i| s.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+		.collect()
+}
+
+fn decode_base32(s: &str) -> Option<Vec<u8>> {
+	let mut bytes  = Vec::with_capacity(s.len() * 5 / 8);
+	let mut buffer = 0u64;
+	let mut bits   = 0;
+	
+	for c in s.bytes() {
+		let val = match c.to_ascii_uppercase() {
+			c @ b'A'..=b'Z' => c - b'A',
+			c @ b'2'..=b'7' => c - b'2' + 26,
+			_ => return None,
+		};
+		
+		buffer = (buffer << 5) | val as u64;
+		bits += 5;
+		
+		if bits >= 8 {
+			bits -= 8;
+			bytes.push((buffer >> bits) as u8);
+		}
+	}
+	
+	Some(bytes)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	fn test_parse() {
+		let magnet = MagnetLink::parse(
+			"magnet:?xt=urn:btih:942f87ce25686c58318bdc4933316d7f63c23ac5&dn=hybrid.bin\
+			 &tr=http%3A%2F%2Ftracker.example.com%2Fannounce&tr=udp%3A%2F%2Ftracker.example.org%3A6969"
+		).unwrap();
+		
+		assert_eq!(magnet.info_hash, decode_hex("942f87ce25686c58318bdc4933316d7f63c23ac5").unwrap());
+		assert_eq!(magnet.display_name.as_deref(), Some("hybrid.bin"));
+		assert_eq!(magnet.trackers, vec![
+			String::from("http://tracker.example.com/announce"),
+			String::from("udp://tracker.example.org:6969"),
+		]);
+	}
+	
+	#[test]
+	fn test_parse_base32() {
+		let hex    = MagnetLink::parse("magnet:?xt=urn:btih:942f87ce25686c58318bdc4933316d7f63c23ac5").unwrap();
+		let base32 = MagnetLink::parse("magnet:?xt=urn:btih:SQXYPTRFNBWFQMML3RETGMLNP5R4EOWF").unwrap();
+		
+		assert_eq!(hex.info_hash, base32.info_hash);
+	}
+	
+	#[test]
+	fn test_parse_invalid() {
+		assert!(MagnetLink::parse("http://example.com").is_err());
+		assert!(MagnetLink::parse("magnet:?dn=foo").is_err());
+		assert!(MagnetLink::parse("magnet:?xt=urn:btih:1234").is_err());
+	}
+}
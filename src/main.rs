@@ -1,15 +1,11 @@
-use std::path::Path;
-
-use reqwest::Client;
-use bendy::encoding::{ToBencode, Error};
-
 use acorntorrent::metainfo;
 use acorntorrent::torrent;
 use acorntorrent::tracker;
 use acorntorrent::config;
 
 
-fn main() -> Result<(), String> {
+#[tokio::main]
+async fn main() -> Result<(), String> {
 	// let cmi = metainfo::TInfo {
 	// 	piece_length: 5,
 	// 	pieces: vec![34, 56, 45, 75, 0, 11, 23, 89, 11, 3],
@@ -26,17 +22,12 @@ fn main() -> Result<(), String> {
 	// 	Err(e) => println!("{:?}", e.to_string()),
 	// }
 	
-	let cl = Client::new();
-	let ns = config::NetworkSettings {
-		ip: None,
-		port: 6000,
-	};
+	let ns = config::NetworkSettings::default();
+	let cl = ns.build_client().map_err(|e| e.to_string())?;
 	
-	let mi = metainfo::BMetainfo::from_path(Path::new("test3.torrent")).unwrap();
-	let ih = metainfo.info.compute_hash()
-		.map_err(|e| e.to_string())?;
-	let bt = torrent::BTorrent::new(mi, ih).unwrap();
-	let tr = tracker::announce(&cl, &bt, None, &ns);
+	let mi = metainfo::BMetainfo::from_path("test3.torrent").unwrap();
+	let bt = torrent::BTorrent::new(mi)?;
+	let tr = tracker::announce_any(&cl, &bt, None, &ns).await;
 	
 	println!("Torrent: {:#?}", tr);
 	
@@ -0,0 +1,132 @@
+// Identifies the client a peer is running from its peer id, for display.
+// 
+// Most clients start their peer id with an identifying prefix in one of a few styles:
+//   Azureus: `-qB4600-`, a dash, two letters for the client, four version characters, a dash.
+//   Shadow:  `S58B-----`, one letter for the client, then up to five version characters,
+//            padded with dashes.
+//   Mainline: `M7-4-3--`, an `M`, then the version numbers separated by dashes.
+// 
+// Version characters are `0`-`9`, then `A`-`Z` for 10-35.
+
+
+// Azureus-style client codes. Not exhaustive; just the clients commonly seen in swarms.
+const AZUREUS_CLIENTS: &[(&[u8; 2], &str)] = &[
+	(b"AZ", "Vuze"),
+	(b"BC", "BitComet"),
+	(b"BI", "BiglyBT"),
+	(b"BT", "BitTorrent"),
+	(b"DE", "Deluge"),
+	(b"KT", "KTorrent"),
+	(b"LT", "libTorrent"),
+	(b"lt", "libtorrent"),
+	(b"qB", "qBittorrent"),
+	(b"TR", "Transmission"),
+	(b"UM", "µTorrent Mac"),
+	(b"UT", "µTorrent"),
+	(b"WW", "WebTorrent"),
+];
+
+// Shadow-style client codes.
+const SHADOW_CLIENTS: &[(u8, &str)] = &[
+	(b'A', "ABC"),
+	(b'O', "Osprey Permaseed"),
+	(b'Q', "BTQueue"),
+	(b'R', "Tribler"),
+	(b'S', "Shadow"),
+	(b'T', "BitTornado"),
+	(b'U', "UPnP NAT Bit Torrent"),
+];
+
+// The client name and version encoded in `peer_id`, e.g. "qBittorrent 4.6.0".
+// `None` if the client isn't one we recognise.
+pub fn decode_peer_id_client(peer_id: &[u8]) -> Option<String> {
+	decode_azureus(peer_id)
+		.or_else(|| decode_mainline(peer_id))
+		.or_else(|| decode_shadow(peer_id))
+}
+
+fn decode_azureus(peer_id: &[u8]) -> Option<String> {
+	if peer_id.len() < 8 || peer_id[0] != b'-' || peer_id[7] != b'-' {
+		return None;
+	}
+	
+	let (_, client) = AZUREUS_CLIENTS.iter().find(|(code, _)| code[..] == peer_id[1..3])?;
+	let version: Vec<u32> = peer_id[3..7].iter().map(|&c| version_digit(c)).collect::<Option<_>>()?;
+	
+	// The fourth character is a build number, which clients don't show. Transmission before
+	// 4.0 used two digits for the minor version (`-TR2940-` is 2.94).
+	let version = match (peer_id[1..3] == b"TR"[..], version[0] < 4) {
+		(true, true) => format!("{}.{}{}", version[0], version[1], version[2]),
+		_            => format!("{}.{}.{}", version[0], version[1], version[2]),
+	};
+	
+	Some(format!("{} {}", client, version))
+}
+
+fn decode_shadow(peer_id: &[u8]) -> Option<String> {
+	let (_, client) = SHADOW_CLIENTS.iter().find(|(code, _)| Some(code) == peer_id.first())?;
+	
+	let version: Vec<u32> = peer_id.iter()
+		.skip(1)
+		.take(5)
+		.take_while(|&&c| c != b'-')
+		.map(|&c| version_digit(c))
+		.collect::<Option<_>>()?;
+	
+	// The version is followed by dashes, to tell it apart from a random peer id.
+	if version.is_empty() || peer_id.get(1 + version.len()..6)?.iter().any(|&c| c != b'-') {
+		return None;
+	}
+	
+	let version: Vec<String> = version.iter().map(u32::to_string).collect();
+	
+	Some(format!("{} {}", client, version.join(".")))
+}
+
+fn decode_mainline(peer_id: &[u8]) -> Option<String> {
+	if peer_id.first() != Some(&b'M') {
+		return None;
+	}
+	
+	let prefix = std::str::from_utf8(peer_id.get(1..8)?).ok()?;
+	let version: Vec<&str> = prefix.trim_end_matches('-').split('-').collect();
+	
+	if version.len() != 3 || version.iter().any(|v| v.is_empty() || !v.bytes().all(|b| b.is_ascii_digit())) {
+		return None;
+	}
+	
+	Some(format!("Mainline {}", version.join(".")))
+}
+
+fn version_digit(c: u8) -> Option<u32> {
+	match c {
+		b'0'..=b'9' => Some(u32::from(c - b'0')),
+		b'A'..=b'Z' => Some(u32::from(c - b'A') + 10),
+		_ => None,
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	fn test_decode_peer_id_client() {
+		assert_eq!(decode_peer_id_client(b"-qB4600-8p!zE2w(b1eF"), Some(String::from("qBittorrent 4.6.0")));
+		assert_eq!(decode_peer_id_client(b"-TR4040-x1u0bjzwhcqb"), Some(String::from("Transmission 4.0.4")));
+		assert_eq!(decode_peer_id_client(b"-TR2940-k3ec7l4h8dzu"), Some(String::from("Transmission 2.94")));
+		assert_eq!(decode_peer_id_client(b"-DE13F0-(d.Zv4uy0Ca6"), Some(String::from("Deluge 1.3.15")));
+		assert_eq!(decode_peer_id_client(b"S58B-----abcdefghijk"), Some(String::from("Shadow 5.8.11")));
+		assert_eq!(decode_peer_id_client(b"M7-4-3--abcdefghijkl"), Some(String::from("Mainline 7.4.3")));
+	}
+	
+	#[test]
+	fn test_decode_peer_id_unknown() {
+		assert_eq!(decode_peer_id_client(b"-ZZ1000-abcdefghijkl"), None);
+		assert_eq!(decode_peer_id_client(b"-qB4600"), None);
+		assert_eq!(decode_peer_id_client(b"Sabcdefghijklmnopqrs"), None);
+		assert_eq!(decode_peer_id_client(&[0xff; 20]), None);
+		assert_eq!(decode_peer_id_client(b""), None);
+	}
+}
@@ -1,12 +1,63 @@
+use std::fmt;
+use std::net::IpAddr;
+
 use rand::Rng;
+use reqwest::{Client, Url};
 use percent_encoding;
 
-use crate::metainfo::BMetainfo;
+use crate::config::NetworkSettings;
+use crate::magnet::MagnetLink;
+use crate::metainfo::{redact_tracker_url, BMetainfo};
+use crate::tracker::{announced_port, BAnnounceEvent};
+
+
+// A torrent's metadata, which may not be known yet for torrents added from a magnet link.
+pub enum PartialInfo {
+	Full(Box<BMetainfo>),
+	
+	// Waiting on the metadata to be fetched from peers (BEP 9). `metadata_size` is the size in
+	// bytes of the info dictionary, once a peer has told us it in the extension handshake.
+	// `trackers` are those from the magnet link, in order, as the metadata's aren't known yet.
+	PendingFromMagnet {
+		info_hash: Vec<u8>,
+		trackers: Vec<String>,
+		metadata_size: Option<u64>,
+	},
+}
+
+// Tracker urls are redacted, as in `BMetainfo`'s `Debug`, since they may hold a passkey.
+impl fmt::Debug for PartialInfo {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			PartialInfo::Full(metainfo) => f.debug_tuple("Full").field(metainfo).finish(),
+			PartialInfo::PendingFromMagnet { info_hash, trackers, metadata_size } => f.debug_struct("PendingFromMagnet")
+				.field("info_hash", info_hash)
+				.field("trackers", &trackers.iter().map(|url| redact_tracker_url(url)).collect::<Vec<_>>())
+				.field("metadata_size", metadata_size)
+				.finish(),
+		}
+	}
+}
+
+impl PartialInfo {
+	pub fn metainfo(&self) -> Option<&BMetainfo> {
+		match self {
+			PartialInfo::Full(metainfo) => Some(metainfo),
+			PartialInfo::PendingFromMagnet { .. } => None,
+		}
+	}
+	
+	pub fn is_pending(&self) -> bool {
+		matches!(self, PartialInfo::PendingFromMagnet { .. })
+	}
+}
 
 
-#[derive(Debug)]
 pub struct BTorrent {
-	pub metainfo: BMetainfo,
+	pub info: PartialInfo,
+	
+	// The tracker to announce to.
+	pub announce: String,
 	
 	pub info_hash: Vec<u8>,
 	pub encoded_info_hash: String,
@@ -14,15 +65,233 @@ pub struct BTorrent {
 	pub peer_id: Vec<u8>,
 	pub encoded_peer_id: String,
 	
+	// Lifetime totals, which are what's reported to trackers.
 	pub uploaded: u64,
 	pub downloaded: u64,
 	pub left: u64,
+	
+	// Totals since the torrent was last started, for display.
+	pub session_uploaded: u64,
+	pub session_downloaded: u64,
+	
+	// How many peers to ask trackers for. If unset, trackers pick (usually 50).
+	pub numwant: Option<u32>,
+	
+	// Adjusts `numwant` as the number of connected peers changes, if set.
+	pub numwant_strategy: Option<NumwantStrategy>,
+}
+
+// Asks trackers for more peers while we have too few, and fewer once we have enough, so a
+// well-connected torrent doesn't have trackers send peers it has no use for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumwantStrategy {
+	// The number of connected peers we're aiming for.
+	pub target_peers: usize,
+	
+	pub min_numwant: u32,
+	pub max_numwant: u32,
+}
+
+impl Default for NumwantStrategy {
+	fn default() -> Self {
+		NumwantStrategy {
+			target_peers: 50,
+			min_numwant:  25,
+			max_numwant:  200,
+		}
+	}
+}
+
+impl NumwantStrategy {
+	// What trackers use when `numwant` isn't sent, and so where adjustments start from.
+	pub const DEFAULT_NUMWANT: u32 = 50;
+	
+	// The `numwant` for the next announce: double the current one if we have fewer peers than
+	// the target, or half of it otherwise.
+	pub fn next_numwant(&self, numwant: u32, peer_count: usize) -> u32 {
+		let next = match peer_count < self.target_peers {
+			true  => numwant.saturating_mul(2),
+			false => numwant / 2,
+		};
+		
+		next.clamp(self.min_numwant, self.max_numwant)
+	}
 }
 
+// The announce url is redacted, as in `BMetainfo`'s `Debug`, since it may hold a passkey.
+impl fmt::Debug for BTorrent {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("BTorrent")
+			.field("info", &self.info)
+			.field("announce", &redact_tracker_url(&self.announce))
+			.field("info_hash", &self.info_hash)
+			.field("encoded_info_hash", &self.encoded_info_hash)
+			.field("peer_id", &self.peer_id)
+			.field("encoded_peer_id", &self.encoded_peer_id)
+			.field("uploaded", &self.uploaded)
+			.field("downloaded", &self.downloaded)
+			.field("left", &self.left)
+			.field("session_uploaded", &self.session_uploaded)
+			.field("session_downloaded", &self.session_downloaded)
+			.field("numwant", &self.numwant)
+			.field("numwant_strategy", &self.numwant_strategy)
+			.finish()
+	}
+}
+
+// The query parameters of an HTTP announce, in order, kept apart by whether they're already
+// url encoded. `reqwest` (and the `serde_urlencoded` library it relies on) can't url encode raw
+// bytes, so the binary `info_hash` and `peer_id` are encoded up front; the rest aren't.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnounceParams {
+	pub encoded: Vec<(String, String)>,
+	pub plain: Vec<(String, String)>,
+}
+
+
 impl BTorrent {
-	pub fn new(metainfo: BMetainfo, info_hash: Vec<u8>) -> BTorrent {
-		let info_hash = metainfo.info.compute_hash()
+	pub fn new(metainfo: BMetainfo) -> Result<BTorrent, String> {
+		let info_hash = metainfo.info_hash()
 			.map_err(|e| e.to_string())?;
+		let announce = metainfo.announce.clone();
+		
+		Ok(BTorrent::from_parts(PartialInfo::Full(Box::new(metainfo)), announce, info_hash))
+	}
+	
+	// Creates a torrent from a magnet link. If the magnet links to the .torrent file itself
+	// (`xs`), and it can be fetched, the torrent has its full metadata. Otherwise, the torrent
+	// only has its info hash and trackers, which is still enough to join the swarm, and the
+	// metadata can be fetched from peers later.
+	pub async fn from_magnet(uri: &str, client: &Client, network_settings: &NetworkSettings) -> Result<BTorrent, String> {
+		let magnet = MagnetLink::parse(uri)?;
+		
+		for url in &magnet.exact_sources {
+			if let Some(metainfo) = fetch_metainfo(client, url, &magnet.info_hash, network_settings).await {
+				return BTorrent::new(metainfo);
+			}
+		}
+		
+		let announce = magnet.trackers.first()
+			.cloned()
+			.ok_or_else(|| String::from("magnet link has no trackers"))?;
+		
+		let info = PartialInfo::PendingFromMagnet {
+			info_hash: magnet.info_hash.clone(),
+			trackers: magnet.trackers,
+			metadata_size: None,
+		};
+		
+		Ok(BTorrent::from_parts(info, announce, magnet.info_hash))
+	}
+	
+	// Fills in the metadata of a torrent added from a magnet link, once it has been fetched.
+	// Errors if the metadata is for a different torrent.
+	pub fn complete_metadata(&mut self, metainfo: BMetainfo) -> Result<(), String> {
+		let info_hash = metainfo.info_hash()
+			.map_err(|e| e.to_string())?;
+		
+		if info_hash != self.info_hash {
+			return Err(String::from("metadata does not match the torrent's info hash"));
+		}
+		
+		self.left = metainfo.info.content_size_bytes();
+		self.info = PartialInfo::Full(Box::new(metainfo));
+		
+		Ok(())
+	}
+	
+	// Records bytes of content sent to peers.
+	pub fn add_uploaded(&mut self, bytes: u64) {
+		self.uploaded         = self.uploaded.saturating_add(bytes);
+		self.session_uploaded = self.session_uploaded.saturating_add(bytes);
+	}
+	
+	// Records bytes of content received from peers.
+	pub fn add_downloaded(&mut self, bytes: u64) {
+		self.downloaded         = self.downloaded.saturating_add(bytes);
+		self.session_downloaded = self.session_downloaded.saturating_add(bytes);
+	}
+	
+	// Starts a new session.
+	pub fn reset_session(&mut self) {
+		self.session_uploaded   = 0;
+		self.session_downloaded = 0;
+	}
+	
+	// Records how many peers we're connected to, adjusting `numwant` for the next announce
+	// if there's a `numwant_strategy`.
+	pub fn update_peer_count(&mut self, peer_count: usize) {
+		if let Some(strategy) = &self.numwant_strategy {
+			let numwant = self.numwant.unwrap_or(NumwantStrategy::DEFAULT_NUMWANT);
+			self.numwant = Some(strategy.next_numwant(numwant, peer_count));
+		}
+	}
+	
+	// Zeroes every counter, as if the torrent had just been added with nothing downloaded.
+	pub fn reset_stats(&mut self) {
+		self.uploaded   = 0;
+		self.downloaded = 0;
+		self.left       = self.info.metainfo()
+			.map(|m| m.info.content_size_bytes())
+			.unwrap_or(0);
+		
+		self.reset_session();
+	}
+	
+	// The query parameters of an HTTP announce.
+	pub fn announce_params(
+		&self,
+		event: Option<BAnnounceEvent>,
+		network_settings: &NetworkSettings)
+	-> AnnounceParams {
+		let encoded = vec![
+			(String::from("info_hash"), self.encoded_info_hash.clone()),
+			(String::from("peer_id"),   self.encoded_peer_id.clone()),
+		];
+		
+		// We always ask for the compact peer list (BEP 23), as some trackers reject `compact=0`.
+		let mut params = vec![
+			(String::from("compact"),    String::from("1")),
+			(String::from("port"),       announced_port(network_settings).to_string()),
+			(String::from("uploaded"),   self.uploaded.to_string()),
+			(String::from("downloaded"), self.downloaded.to_string()),
+			(String::from("left"),       self.left.to_string()),
+		];
+		
+		// Optional keys.
+		if let Some(ip) = &network_settings.ip {
+			params.push((String::from("ip"), ip.to_string()));
+		}
+		
+		if let Some(numwant) = self.numwant {
+			params.push((String::from("numwant"), numwant.to_string()));
+		}
+		
+		// Optional keys advertising support for (or a requirement of) encrypted peer connections.
+		if network_settings.support_crypto {
+			params.push((String::from("supportcrypto"), String::from("1")));
+		}
+		
+		if network_settings.require_crypto {
+			params.push((String::from("requirecrypto"), String::from("1")));
+		}
+		
+		// The `event` key is only necessary if the announce is not for one of the
+		// regular announces performed while a torrent is active.
+		if let Some(event) = event {
+			let val = match event {
+				BAnnounceEvent::Started   => "started",
+				BAnnounceEvent::Completed => "completed",
+				BAnnounceEvent::Stopped   => "stopped",
+				BAnnounceEvent::Paused    => "paused",
+			};
+			params.push((String::from("event"), String::from(val)));
+		}
+		
+		AnnounceParams { encoded, plain: params }
+	}
+	
+	fn from_parts(info: PartialInfo, announce: String, info_hash: Vec<u8>) -> BTorrent {
 		let encoded_info_hash = percent_encoding::percent_encode(
 			&info_hash,
 			percent_encoding::NON_ALPHANUMERIC
@@ -34,8 +303,13 @@ impl BTorrent {
 			percent_encoding::NON_ALPHANUMERIC
 		).to_string();
 		
-		Ok(BTorrent {
-			metainfo,
+		let left = info.metainfo()
+			.map(|m| m.info.content_size_bytes())
+			.unwrap_or(0);
+		
+		BTorrent {
+			info,
+			announce,
 			
 			info_hash,
 			encoded_info_hash,
@@ -45,7 +319,306 @@ impl BTorrent {
 			
 			uploaded: 0,
 			downloaded: 0,
-			left: 0,
-		})
+			left,
+			
+			session_uploaded: 0,
+			session_downloaded: 0,
+			
+			numwant: None,
+			numwant_strategy: None,
+		}
+	}
+}
+
+// Fetches a .torrent file, discarding it if it fails to parse or isn't the torrent we expect.
+// The request is made like an HTTP announce, since the file is often hosted by the tracker.
+async fn fetch_metainfo(
+	client: &Client,
+	url: &str,
+	info_hash: &[u8],
+	network_settings: &NetworkSettings)
+-> Option<BMetainfo> {
+	let parsed = Url::parse(url).ok()?;
+	
+	// A host given as an address can only be reached over that address's IP version.
+	if let Some(Ok(ip)) = parsed.host_str().map(|h| h.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>()) {
+		if !network_settings.address_family.allows(&ip) {
+			return None;
+		}
+	}
+	
+	let mut request = client.get(parsed);
+	
+	for (name, value) in &network_settings.extra_headers {
+		request = request.header(name, value);
+	}
+	
+	let response = request.send().await.ok()?;
+	let bytes = response.bytes().await.ok()?;
+	let metainfo = BMetainfo::from_bytes(&bytes).ok()?;
+	
+	if metainfo.info_hash().ok()? != info_hash {
+		return None;
+	}
+	
+	Some(metainfo)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	use crate::config::AddressFamily;
+	use crate::tracker;
+	
+	const HYBRID_INFO_HASH: [u8; 20] = [
+		0x94, 0x2f, 0x87, 0xce, 0x25, 0x68, 0x6c, 0x58, 0x31, 0x8b,
+		0xdc, 0x49, 0x33, 0x31, 0x6d, 0x7f, 0x63, 0xc2, 0x3a, 0xc5,
+	];
+	
+	#[tokio::test]
+	async fn test_from_magnet() {
+		let client = Client::new();
+		let torrent = BTorrent::from_magnet(
+			"magnet:?xt=urn:btih:942f87ce25686c58318bdc4933316d7f63c23ac5\
+			 &tr=http%3A%2F%2Ftracker.example.com%2Fannounce\
+			 &tr=udp%3A%2F%2Ftracker.example.com%3A6969%2Fannounce",
+			&client,
+			&NetworkSettings::default(),
+		).await.unwrap();
+		
+		assert_eq!(torrent.info_hash, HYBRID_INFO_HASH);
+		assert_eq!(torrent.announce, "http://tracker.example.com/announce");
+		assert!(torrent.info.is_pending());
+		assert!(torrent.info.metainfo().is_none());
+		assert!(matches!(&torrent.info, PartialInfo::PendingFromMagnet { trackers, .. } if *trackers == vec![
+			String::from("http://tracker.example.com/announce"),
+			String::from("udp://tracker.example.com:6969/announce"),
+		]));
+	}
+	
+	#[tokio::test]
+	async fn test_announce_pending() {
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+		use wiremock::matchers::{method, path, query_param};
+		
+		let server = MockServer::start().await;
+		Mock::given(method("GET"))
+			.and(path("/announce"))
+			.and(query_param("info_hash", String::from_utf8_lossy(&HYBRID_INFO_HASH)))
+			.respond_with(ResponseTemplate::new(200).set_body_bytes(&b"d8:intervali1800e5:peers0:e"[..]))
+			.mount(&server)
+			.await;
+		
+		let client = Client::new();
+		let uri = format!(
+			"magnet:?xt=urn:btih:942f87ce25686c58318bdc4933316d7f63c23ac5&tr={}/announce",
+			server.uri(),
+		);
+		let ns = NetworkSettings::default();
+		let torrent = BTorrent::from_magnet(&uri, &client, &ns).await.unwrap();
+		
+		assert!(torrent.info.is_pending());
+		
+		let response = tracker::announce(&client, &torrent, None, &ns).await.unwrap();
+		assert_eq!(response.interval, 1800);
+		
+		// Every tracker in the magnet link is tried, not just the first.
+		let uri = format!(
+			"magnet:?xt=urn:btih:942f87ce25686c58318bdc4933316d7f63c23ac5&tr=http://127.0.0.1:1/announce&tr={}/announce",
+			server.uri(),
+		);
+		let torrent = BTorrent::from_magnet(&uri, &client, &ns).await.unwrap();
+		
+		let (url, _) = tracker::announce_all_tiers(&client, &torrent, None, &ns).await.unwrap();
+		assert_eq!(url, format!("{}/announce", server.uri()));
+		
+		let gathered = tracker::announce_gather(&client, &torrent, None, &ns).await.unwrap();
+		assert_eq!(gathered.responded_tracker, url);
+		assert_eq!(gathered.failures.len(), 1);
+	}
+	
+	#[tokio::test]
+	async fn test_numwant_strategy() {
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+		use wiremock::matchers::{method, path};
+		
+		let server = MockServer::start().await;
+		Mock::given(method("GET"))
+			.and(path("/announce"))
+			.respond_with(ResponseTemplate::new(200).set_body_bytes(&b"d8:intervali1800e5:peers0:e"[..]))
+			.mount(&server)
+			.await;
+		
+		let mut torrent = BTorrent::new(BMetainfo::from_path("test_torrents/single_file.torrent").unwrap()).unwrap();
+		torrent.announce = format!("{}/announce", server.uri());
+		torrent.numwant_strategy = Some(NumwantStrategy::default());
+		
+		let client = Client::new();
+		let ns = NetworkSettings::default();
+		
+		// Few peers, so ask for more.
+		torrent.update_peer_count(3);
+		tracker::announce(&client, &torrent, None, &ns).await.unwrap();
+		
+		// Saturated, so ask for fewer.
+		torrent.update_peer_count(80);
+		tracker::announce(&client, &torrent, None, &ns).await.unwrap();
+		
+		let numwants: Vec<String> = server.received_requests().await.unwrap().iter()
+			.map(|r| r.url.query_pairs().find(|(k, _)| k == "numwant").unwrap().1.into_owned())
+			.collect();
+		assert_eq!(numwants, vec!["100", "50"]);
+		
+		// Bounded by the strategy's limits.
+		let strategy = NumwantStrategy::default();
+		assert_eq!(strategy.next_numwant(150, 0), 200);
+		assert_eq!(strategy.next_numwant(30, 1000), 25);
+	}
+	
+	#[tokio::test]
+	async fn test_debug_redacts_passkeys() {
+		let mut torrent = BTorrent::new(BMetainfo::from_path("test_torrents/single_file.torrent").unwrap()).unwrap();
+		torrent.announce = String::from("http://tracker.example.com/announce.php?passkey=0123456789abcdef");
+		
+		let debug = format!("{:?}", torrent);
+		assert!(!debug.contains("0123456789abcdef"));
+		assert!(debug.contains("\"http://tracker.example.com/announce.php?***\""));
+		
+		let torrent = BTorrent::from_magnet(
+			"magnet:?xt=urn:btih:942f87ce25686c58318bdc4933316d7f63c23ac5\
+			 &tr=http%3A%2F%2Ftracker.example.com%2F0123456789abcdef0123456789abcdef%2Fannounce",
+			&Client::new(),
+			&NetworkSettings::default(),
+		).await.unwrap();
+		
+		let debug = format!("{:?}", torrent);
+		assert!(!debug.contains("0123456789abcdef"));
+		assert!(debug.contains("trackers: [\"http://tracker.example.com/***/announce\"]"));
+	}
+	
+	#[test]
+	fn test_complete_metadata() {
+		let mut torrent = BTorrent::from_parts(
+			PartialInfo::PendingFromMagnet {
+				info_hash: HYBRID_INFO_HASH.to_vec(),
+				trackers: vec![String::from("http://tracker.example.com/announce")],
+				metadata_size: None,
+			},
+			String::from("http://tracker.example.com/announce"),
+			HYBRID_INFO_HASH.to_vec(),
+		);
+		
+		let wrong = BMetainfo::from_path("test_torrents/single_file.torrent").unwrap();
+		assert!(torrent.complete_metadata(wrong).is_err());
+		assert!(torrent.info.is_pending());
+		
+		let right = BMetainfo::from_path("test_torrents/hybrid_single_file.torrent").unwrap();
+		torrent.complete_metadata(right).unwrap();
+		assert!(!torrent.info.is_pending());
+		assert_eq!(torrent.left, 40000);
+	}
+	
+	#[test]
+	fn test_announce_params() {
+		let metainfo = BMetainfo::from_path("test_torrents/hybrid_single_file.torrent").unwrap();
+		let mut torrent = BTorrent::new(metainfo).unwrap();
+		torrent.add_uploaded(100);
+		
+		let ns = NetworkSettings {
+			ip: Some("2001:db8::1".parse().unwrap()),
+			support_crypto: true,
+			..NetworkSettings::default()
+		};
+		
+		let params = torrent.announce_params(Some(BAnnounceEvent::Started), &ns);
+		
+		assert_eq!(params.encoded, vec![
+			(String::from("info_hash"), String::from("%94%2F%87%CE%25hlX1%8B%DCI31m%7Fc%C2%3A%C5")),
+			(String::from("peer_id"),   torrent.encoded_peer_id.clone()),
+		]);
+		
+		let plain: Vec<(&str, &str)> = params.plain.iter()
+			.map(|(k, v)| (k.as_str(), v.as_str()))
+			.collect();
+		
+		assert_eq!(plain, vec![
+			("compact",       "1"),
+			("port",          "6881"),
+			("uploaded",      "100"),
+			("downloaded",    "0"),
+			("left",          "40000"),
+			("ip",            "2001:db8::1"),
+			("supportcrypto", "1"),
+			("event",         "started"),
+		]);
+	}
+	
+	#[test]
+	fn test_announce_params_paused() {
+		let metainfo = BMetainfo::from_path("test_torrents/hybrid_single_file.torrent").unwrap();
+		let torrent = BTorrent::new(metainfo).unwrap();
+		
+		let params = torrent.announce_params(Some(BAnnounceEvent::Paused), &NetworkSettings::default());
+		assert_eq!(params.plain.last(), Some(&(String::from("event"), String::from("paused"))));
+	}
+	
+	#[test]
+	fn test_reset_stats() {
+		let metainfo = BMetainfo::from_path("test_torrents/hybrid_single_file.torrent").unwrap();
+		let mut torrent = BTorrent::new(metainfo).unwrap();
+		assert_eq!(torrent.left, 40000);
+		
+		torrent.add_downloaded(16384);
+		torrent.add_uploaded(100);
+		torrent.left = 40000 - 16384;
+		
+		torrent.reset_session();
+		torrent.add_uploaded(50);
+		assert_eq!((torrent.uploaded, torrent.session_uploaded), (150, 50));
+		assert_eq!((torrent.downloaded, torrent.session_downloaded), (16384, 0));
+		
+		torrent.reset_stats();
+		assert_eq!(torrent.left, 40000);
+		assert_eq!((torrent.uploaded, torrent.downloaded), (0, 0));
+		assert_eq!((torrent.session_uploaded, torrent.session_downloaded), (0, 0));
+	}
+	
+	#[tokio::test]
+	async fn test_from_magnet_exact_source() {
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+		use wiremock::matchers::{header, method, path};
+		
+		let server = MockServer::start().await;
+		Mock::given(method("GET"))
+			.and(path("/hybrid.torrent"))
+			.and(header("Cookie", "uid=1"))
+			.respond_with(ResponseTemplate::new(200).set_body_bytes(
+				std::fs::read("test_torrents/hybrid_single_file.torrent").unwrap()
+			))
+			.mount(&server)
+			.await;
+		
+		let client = Client::new();
+		let uri = format!(
+			"magnet:?xt=urn:btih:942f87ce25686c58318bdc4933316d7f63c23ac5&xs={}/hybrid.torrent",
+			server.uri(),
+		);
+		let mut ns = NetworkSettings::default();
+		ns.extra_headers.push((String::from("Cookie"), String::from("uid=1")));
+		
+		let torrent = BTorrent::from_magnet(&uri, &client, &ns).await.unwrap();
+		
+		assert_eq!(torrent.info_hash, HYBRID_INFO_HASH);
+		assert_eq!(torrent.announce, "http://tracker.example.com/announce");
+		assert!(torrent.info.metainfo().is_some());
+		
+		// The file is on an IPv4 address, so can't be fetched over IPv6 alone.
+		ns.address_family = AddressFamily::V6;
+		
+		let uri = format!("{}&tr=http%3A%2F%2Ftracker.example.com%2Fannounce", uri);
+		let torrent = BTorrent::from_magnet(&uri, &client, &ns).await.unwrap();
+		assert!(torrent.info.is_pending());
 	}
 }
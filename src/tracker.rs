@@ -1,87 +1,786 @@
+use std::collections::{BTreeMap, HashSet};
 use std::convert::TryFrom;
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
 
-use reqwest::Client;
+use rand::Rng;
+use reqwest::{Client, RequestBuilder, StatusCode, Url};
+use tokio::net::UdpSocket;
+use percent_encoding;
 use bendy::{
 	decoding::{FromBencode, Decoder, Object, Error as DecodingError, ResultExt},
 	encoding::AsString,
 };
 use failure::err_msg;
 
-use crate::torrent::BTorrent;
-use crate::config::NetworkSettings;
+use crate::metainfo::{nesting_depth, MAX_NESTING_DEPTH};
+use crate::torrent::{BTorrent, PartialInfo};
+use crate::config::{AddressFamily, AnnounceHost, NetworkSettings};
 
 
-#[derive(PartialEq)]
+#[derive(Debug)]
+pub enum AnnounceError {
+	// The request couldn't be sent, or the response body couldn't be read.
+	Http(reqwest::Error),
+	
+	// The tracker sent back an empty body.
+	EmptyResponse,
+	
+	// The tracker's response ends part-way through a bencoded value, likely because
+	// the connection was cut off.
+	TruncatedResponse,
+	
+	// The tracker's response is complete, but not a valid tracker response.
+	InvalidResponse(String),
+	
+	// A UDP announce couldn't be sent, or the tracker didn't reply in time.
+	Io(io::Error),
+	
+	// The tracker sent back an error message instead of a response.
+	TrackerError(TrackerFailure),
+	
+	// The announce url isn't one we know how to announce to.
+	UnsupportedUrl(String),
+}
+
+impl fmt::Display for AnnounceError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			AnnounceError::Http(e)            => write!(f, "announce request failed: {}", e),
+			AnnounceError::EmptyResponse      => write!(f, "tracker sent an empty response"),
+			AnnounceError::TruncatedResponse  => write!(f, "tracker response was truncated"),
+			AnnounceError::InvalidResponse(e) => write!(f, "invalid tracker response: {}", e),
+			AnnounceError::Io(e)              => write!(f, "announce request failed: {}", e),
+			AnnounceError::TrackerError(e)    => write!(f, "tracker returned an error: {}", e.reason),
+			AnnounceError::UnsupportedUrl(u)  => write!(f, "unsupported announce url: {}", u),
+		}
+	}
+}
+
+impl AnnounceError {
+	// How long the tracker asked us to wait before announcing again, if it failed the announce.
+	pub fn retry_in(&self) -> Option<isize> {
+		match self {
+			AnnounceError::TrackerError(failure) => failure.retry_in,
+			_ => None,
+		}
+	}
+}
+
+
+// A tracker's refusal of an announce, e.g., because the torrent isn't registered with it.
+#[derive(Debug, PartialEq)]
+pub struct TrackerFailure {
+	pub reason: String,
+	
+	// When to try again, per BEP 31, which gives this in minutes. Trackers that will never
+	// accept the announce send the string `never` instead, which is treated as absent.
+	pub retry_in: Option<isize>,
+}
+
+impl FromBencode for TrackerFailure {
+	fn decode_bencode_object(object: Object) -> Result<Self, DecodingError> {
+		let mut reason   = None;
+		let mut retry_in = None;
+		
+		// Failure responses may carry other keys (e.g., `interval`), which are ignored.
+		let mut dict = object.try_into_dictionary()?;
+		while let Some(keyval) = dict.next_pair()? {
+			match keyval {
+				(b"failure reason", val) => {
+					reason = String::decode_bencode_object(val)
+						.context("failure reason")
+						.map(Some)?;
+				}
+				(b"retry in", Object::Integer(val)) => {
+					retry_in = val.parse::<isize>()
+						.map_err(|_| DecodingError::malformed_content(err_msg("retry in is out of range")))
+						.context("retry in")
+						.map(Some)?;
+				}
+				_ => {}
+			}
+		}
+		
+		let reason = reason.ok_or_else(|| DecodingError::missing_field("failure reason"))?;
+		
+		Ok(TrackerFailure {
+			reason,
+			retry_in,
+		})
+	}
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BAnnounceEvent {
 	Started,
 	Completed,
 	Stopped,
+	
+	// The torrent is paused, but not removed, so the tracker can keep listing it (BEP 21).
+	// Trackers that don't recognise this treat it as a regular announce.
+	Paused,
 }
 
 
+// Announces to the torrent's tracker over whichever protocol its announce url calls for.
+pub async fn announce_any(
+	client: &Client,
+	torrent: &BTorrent,
+	event: Option<BAnnounceEvent>,
+	network_settings: &NetworkSettings)
+-> Result<BTrackerResponse, AnnounceError> {
+	announce_any_to(client, &torrent.announce, torrent, event, network_settings).await
+}
+
+// Announces to every tracker of the torrent in tier order (BEP 12), until one responds.
+// Any failure moves on to the next tracker, including a tracker rejecting the announce
+// (e.g., "torrent not registered"), since other trackers may well accept it. Returns the
+// tracker that responded, or else every tracker's error, in the order they were tried.
+// 
+// Torrents still waiting on their metadata use the trackers from their magnet link.
+pub async fn announce_all_tiers(
+	client: &Client,
+	torrent: &BTorrent,
+	event: Option<BAnnounceEvent>,
+	network_settings: &NetworkSettings)
+-> Result<(String, BTrackerResponse), Vec<(String, AnnounceError)>> {
+	let mut failures = Vec::new();
+	
+	for url in tracker_urls(torrent) {
+		match announce_any_to(client, &url, torrent, event, network_settings).await {
+			Ok(response) => return Ok((url, response)),
+			Err(e)       => failures.push((url, e)),
+		}
+	}
+	
+	Err(failures)
+}
+
+// The combined result of announcing to every tracker of a torrent.
+#[derive(Debug)]
+pub struct GatheredAnnounce {
+	// Every tracker's peers, without duplicates, in the order they were received.
+	pub peers: Vec<BPeer>,
+	
+	// The first tracker to respond, i.e., the one a "working tracker" indicator should show.
+	pub responded_tracker: String,
+	
+	// How many peers each responding tracker sent, in the order they were tried.
+	pub peer_counts: Vec<(String, usize)>,
+	
+	// The trackers that failed, in the order they were tried.
+	pub failures: Vec<(String, AnnounceError)>,
+}
+
+// Announces to every tracker of the torrent, rather than stopping at the first to respond
+// like `announce_all_tiers`, and merges their peers. Fails only if no tracker responds,
+// returning every tracker's error, in the order they were tried.
+pub async fn announce_gather(
+	client: &Client,
+	torrent: &BTorrent,
+	event: Option<BAnnounceEvent>,
+	network_settings: &NetworkSettings)
+-> Result<GatheredAnnounce, Vec<(String, AnnounceError)>> {
+	let mut peers       = Vec::new();
+	let mut seen        = HashSet::new();
+	let mut peer_counts = Vec::new();
+	let mut failures    = Vec::new();
+	
+	for url in tracker_urls(torrent) {
+		match announce_any_to(client, &url, torrent, event, network_settings).await {
+			Ok(response) => {
+				peer_counts.push((url, response.peers.len()));
+				
+				for peer in response.peers {
+					if seen.insert(peer.clone()) {
+						peers.push(peer);
+					}
+				}
+			}
+			Err(e) => failures.push((url, e)),
+		}
+	}
+	
+	match peer_counts.first() {
+		Some((url, _)) => Ok(GatheredAnnounce {
+			peers,
+			responded_tracker: url.clone(),
+			peer_counts,
+			failures,
+		}),
+		None => Err(failures),
+	}
+}
+
+// Every tracker of the torrent, in tier order.
+fn tracker_urls(torrent: &BTorrent) -> Vec<String> {
+	match &torrent.info {
+		PartialInfo::Full(metainfo) => metainfo.all_trackers_flat().into_iter().map(|(_, url)| url).collect(),
+		PartialInfo::PendingFromMagnet { trackers, .. } => trackers.clone(),
+	}
+}
+
+async fn announce_any_to(
+	client: &Client,
+	announce_url: &str,
+	torrent: &BTorrent,
+	event: Option<BAnnounceEvent>,
+	network_settings: &NetworkSettings)
+-> Result<BTrackerResponse, AnnounceError> {
+	let url = Url::parse(announce_url)
+		.map_err(|_| AnnounceError::UnsupportedUrl(announce_url.to_string()))?;
+	
+	match url.scheme() {
+		"http" | "https" => announce_to(client, announce_url, torrent, event, network_settings).await,
+		"udp"            => announce_udp(&url, torrent, event, network_settings).await,
+		_                => Err(AnnounceError::UnsupportedUrl(announce_url.to_string())),
+	}
+}
+
 pub async fn announce(
 	client: &Client,
 	torrent: &BTorrent,
 	event: Option<BAnnounceEvent>,
 	network_settings: &NetworkSettings)
--> Result<reqwest::Response, reqwest::Error> {
-	// `reqwest` (and the `serde_urlencoded` library it relies on) doesn't accept
-	// raw bytes as input to be url encoded, so we need to work around this by manually
-	// url encoding our info hash and peer id, and then manually adding them
-	// to the url used for the `RequestBuilder`.
-	let url = format!("{}?info_hash={}peer_id={}",
-		torrent.metainfo.announce,
-		torrent.encoded_info_hash,
-		torrent.encoded_peer_id,
+-> Result<BTrackerResponse, AnnounceError> {
+	announce_to(client, &torrent.announce, torrent, event, network_settings).await
+}
+
+async fn announce_to(
+	client: &Client,
+	announce_url: &str,
+	torrent: &BTorrent,
+	event: Option<BAnnounceEvent>,
+	network_settings: &NetworkSettings)
+-> Result<BTrackerResponse, AnnounceError> {
+	let mut compact = true;
+	
+	loop {
+		let response = announce_request_to(client, announce_url, torrent, event, network_settings, compact)
+			.send().await
+			.map_err(AnnounceError::Http)?;
+		
+		let status = response.status();
+		let body = response.bytes().await
+			.map_err(AnnounceError::Http)?;
+		
+		match BTrackerResponse::from_announce_body(&body) {
+			// A few old trackers only support the original dictionary peer list, and reject
+			// `compact=1` outright. Such trackers get one retry without it.
+			Err(e) if compact && rejects_compact(status, &body, &e) => compact = false,
+			
+			result => {
+				let mut tracker_response = result?;
+				tracker_response.retain_address_family(network_settings.address_family);
+				
+				return Ok(tracker_response);
+			}
+		}
+	}
+}
+
+// Whether a failed announce looks like the tracker refusing the compact peer list format.
+// A 400 on its own isn't enough, as trackers also send it for a bad passkey or malformed request.
+fn rejects_compact(status: StatusCode, body: &[u8], error: &AnnounceError) -> bool {
+	match error {
+		AnnounceError::TrackerError(failure) => failure.reason.to_lowercase().contains("compact"),
+		_ => status == StatusCode::BAD_REQUEST && String::from_utf8_lossy(body).to_lowercase().contains("compact"),
+	}
+}
+
+// Builds the announce request without sending it.
+pub fn announce_request(
+	client: &Client,
+	torrent: &BTorrent,
+	event: Option<BAnnounceEvent>,
+	network_settings: &NetworkSettings)
+-> RequestBuilder {
+	announce_request_to(client, &torrent.announce, torrent, event, network_settings, true)
+}
+
+// The url an announce would be sent to, without sending it, to check exactly what a tracker
+// would see (e.g., before announcing to a strict private tracker).
+pub fn announce_dry_run(
+	client: &Client,
+	torrent: &BTorrent,
+	event: Option<BAnnounceEvent>,
+	network_settings: &NetworkSettings)
+-> Result<String, AnnounceError> {
+	let request = announce_request(client, torrent, event, network_settings)
+		.build()
+		.map_err(AnnounceError::Http)?;
+	
+	Ok(request.url().to_string())
+}
+
+fn announce_request_to(
+	client: &Client,
+	announce_url: &str,
+	torrent: &BTorrent,
+	event: Option<BAnnounceEvent>,
+	network_settings: &NetworkSettings,
+	compact: bool)
+-> RequestBuilder {
+	// The already url encoded params are added to the url used for the `RequestBuilder`
+	// manually, leaving `reqwest` to encode the rest.
+	let mut params = torrent.announce_params(event, network_settings);
+	
+	if !compact {
+		for (_, value) in params.plain.iter_mut().filter(|(key, _)| key == "compact") {
+			*value = String::from("0");
+		}
+	}
+	
+	// The announce url may already have a query string (e.g., a private tracker passkey).
+	let separator = if announce_url.contains('?') { '&' } else { '?' };
+	let url = format!("{}{}{}",
+		announce_url,
+		separator,
+		params.encoded.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("&"),
 	);
 	
-	let mut request = client.get(&url);
+	let mut request = client.get(&url).query(&params.plain);
+	
+	for (name, value) in &network_settings.extra_headers {
+		request = request.header(name, value);
+	}
+	
+	request
+}
+
+
+// The port peers should connect to us on.
+pub(crate) fn announced_port(network_settings: &NetworkSettings) -> u64 {
+	network_settings.announce_port
+		.map(u64::from)
+		.unwrap_or(network_settings.port)
+}
+
+
+// Magic constant identifying the UDP tracker protocol, from BEP 15.
+const UDP_PROTOCOL_ID: u64 = 0x417_2710_1980;
+
+const UDP_ACTION_CONNECT:  u32 = 0;
+const UDP_ACTION_ANNOUNCE: u32 = 1;
+const UDP_ACTION_ERROR:    u32 = 3;
+
+// How long to wait for each reply from a UDP tracker.
+const UDP_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Announces to a UDP tracker, per BEP 15.
+pub async fn announce_udp(
+	url: &Url,
+	torrent: &BTorrent,
+	event: Option<BAnnounceEvent>,
+	network_settings: &NetworkSettings)
+-> Result<BTrackerResponse, AnnounceError> {
+	let unsupported = || AnnounceError::UnsupportedUrl(url.to_string());
+	let host = url.host_str().ok_or_else(unsupported)?;
+	let port = url.port().ok_or_else(unsupported)?;
 	
-	request = request.query(&[
-			("info_hash",  &torrent.encoded_info_hash),
-			("port",       &network_settings.port.to_string()),
-			("uploaded",   &torrent.uploaded.to_string()),
-			("downloaded", &torrent.downloaded.to_string()),
-			("left",       &torrent.left.to_string()),
-		]);
+	// Resolve the tracker, and bind a socket of the same family to talk to it from.
+	let address_family = network_settings.address_family;
+	let tracker_addr = tokio::net::lookup_host((host, port)).await
+		.map_err(AnnounceError::Io)?
+		.find(|addr| address_family.allows(&addr.ip()))
+		.ok_or_else(|| AnnounceError::Io(io::Error::new(
+			io::ErrorKind::NotFound,
+			"tracker has no address in the allowed address family",
+		)))?;
 	
-	// Optional key.
-	if let Some(ip) = &network_settings.ip {
-		request = request.query(&["ip", ip]);
+	let local_addr = match tracker_addr {
+		SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+		SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+	};
+	
+	let socket = UdpSocket::bind(local_addr).await.map_err(AnnounceError::Io)?;
+	socket.connect(tracker_addr).await.map_err(AnnounceError::Io)?;
+	
+	// Obtain a connection id.
+	let transaction_id = rand::thread_rng().gen::<u32>();
+	
+	let mut request = Vec::with_capacity(16);
+	request.extend_from_slice(&UDP_PROTOCOL_ID.to_be_bytes());
+	request.extend_from_slice(&UDP_ACTION_CONNECT.to_be_bytes());
+	request.extend_from_slice(&transaction_id.to_be_bytes());
+	
+	let reply = udp_exchange(&socket, &request).await?;
+	let body = check_udp_reply(&reply, UDP_ACTION_CONNECT, transaction_id)?;
+	let connection_id = body.get(0..8)
+		.ok_or_else(|| AnnounceError::InvalidResponse(String::from("connect response too short")))?;
+	
+	// Announce.
+	let transaction_id = rand::thread_rng().gen::<u32>();
+	
+	let event = match event {
+		None                            => 0u32,
+		Some(BAnnounceEvent::Completed) => 1,
+		Some(BAnnounceEvent::Started)   => 2,
+		Some(BAnnounceEvent::Stopped)   => 3,
+		
+		// BEP 15 has no paused event, so this is a regular announce.
+		Some(BAnnounceEvent::Paused)    => 0,
+	};
+	
+	// Only an IPv4 address can be sent; otherwise the tracker uses the packet's source address.
+	let ip = match network_settings.ip {
+		Some(AnnounceHost::Ip(IpAddr::V4(ip))) => u32::from(ip),
+		_ => 0,
+	};
+	
+	let port = u16::try_from(announced_port(network_settings))
+		.map_err(|_| AnnounceError::Io(io::Error::new(io::ErrorKind::InvalidInput, "port out of range")))?;
+	
+	// -1 leaves it to the tracker.
+	let num_want = torrent.numwant.map(|n| i32::try_from(n).unwrap_or(i32::MAX)).unwrap_or(-1);
+	
+	let mut request = Vec::with_capacity(98);
+	request.extend_from_slice(connection_id);
+	request.extend_from_slice(&UDP_ACTION_ANNOUNCE.to_be_bytes());
+	request.extend_from_slice(&transaction_id.to_be_bytes());
+	request.extend_from_slice(&torrent.info_hash);
+	request.extend_from_slice(&torrent.peer_id);
+	request.extend_from_slice(&torrent.downloaded.to_be_bytes());
+	request.extend_from_slice(&torrent.left.to_be_bytes());
+	request.extend_from_slice(&torrent.uploaded.to_be_bytes());
+	request.extend_from_slice(&event.to_be_bytes());
+	request.extend_from_slice(&ip.to_be_bytes());
+	request.extend_from_slice(&rand::thread_rng().gen::<u32>().to_be_bytes()); // key
+	request.extend_from_slice(&num_want.to_be_bytes());
+	request.extend_from_slice(&port.to_be_bytes());
+	
+	let reply = udp_exchange(&socket, &request).await?;
+	let body = check_udp_reply(&reply, UDP_ACTION_ANNOUNCE, transaction_id)?;
+	
+	let mut tracker_response = BTrackerResponse::from_udp_announce_body(body, tracker_addr.is_ipv6())?;
+	tracker_response.retain_address_family(address_family);
+	
+	Ok(tracker_response)
+}
+
+// Sends a request to a UDP tracker and waits for its reply.
+async fn udp_exchange(socket: &UdpSocket, request: &[u8]) -> Result<Vec<u8>, AnnounceError> {
+	socket.send(request).await.map_err(AnnounceError::Io)?;
+	
+	// Replies are small, except for announces with long peer lists.
+	let mut buf = vec![0; 65536];
+	let len = tokio::time::timeout(UDP_TIMEOUT, socket.recv(&mut buf)).await
+		.map_err(|_| AnnounceError::Io(io::Error::new(io::ErrorKind::TimedOut, "tracker did not reply")))?
+		.map_err(AnnounceError::Io)?;
+	
+	buf.truncate(len);
+	Ok(buf)
+}
+
+// Checks the header of a UDP tracker reply, returning the rest of the reply.
+fn check_udp_reply(reply: &[u8], action: u32, transaction_id: u32) -> Result<&[u8], AnnounceError> {
+	if reply.is_empty() {
+		return Err(AnnounceError::EmptyResponse);
 	}
 	
-	// The `event` key is only necessary if the announce is not for one of the
-	// regular announces performed while a torrent is active.
-	if let Some(event) = event {
-		let val = match event {
-			BAnnounceEvent::Started   => "started",
-			BAnnounceEvent::Completed => "completed",
-			BAnnounceEvent::Stopped   => "stopped",
-		};
-		request = request.query(&["event", val]);
+	if reply.len() < 8 {
+		return Err(AnnounceError::TruncatedResponse);
+	}
+	
+	let reply_action         = u32::from_be_bytes(<[u8; 4]>::try_from(&reply[0..4]).unwrap());
+	let reply_transaction_id = u32::from_be_bytes(<[u8; 4]>::try_from(&reply[4..8]).unwrap());
+	
+	if reply_transaction_id != transaction_id {
+		return Err(AnnounceError::InvalidResponse(String::from("transaction id mismatch")));
+	}
+	
+	if reply_action == UDP_ACTION_ERROR {
+		return Err(AnnounceError::TrackerError(TrackerFailure {
+			reason: String::from_utf8_lossy(&reply[8..]).into_owned(),
+			retry_in: None,
+		}));
+	}
+	
+	if reply_action != action {
+		return Err(AnnounceError::InvalidResponse(format!("unexpected action {}", reply_action)));
+	}
+	
+	Ok(&reply[8..])
+}
+
+
+// Derives the scrape url from an announce url, per the convention in BEP 48: the last path
+// component must begin with `announce`, which is replaced with `scrape`. Trackers whose
+// announce url doesn't follow this convention don't support scraping.
+pub fn scrape_url(announce_url: &str) -> Option<String> {
+	let last_slash = announce_url.rfind('/')?;
+	let (base, last) = announce_url.split_at(last_slash + 1);
+	
+	last.strip_prefix("announce")
+		.map(|rest| format!("{}scrape{}", base, rest))
+}
+
+// Scrapes several torrents from the same tracker in a single request.
+pub async fn scrape_many(
+	client: &Client,
+	info_hashes: &[&[u8]],
+	announce_url: &str)
+-> Result<ScrapeResponse, String> {
+	let mut url = scrape_url(announce_url)
+		.ok_or_else(|| format!("tracker '{}' does not support scraping", announce_url))?;
+	
+	// As with announces, the info hashes are raw bytes, so must be url encoded manually.
+	for (i, info_hash) in info_hashes.iter().enumerate() {
+		let separator = if i == 0 && !url.contains('?') { '?' } else { '&' };
+		let encoded = percent_encoding::percent_encode(info_hash, percent_encoding::NON_ALPHANUMERIC);
+		url.push_str(&format!("{}info_hash={}", separator, encoded));
+	}
+	
+	let response = client.get(&url).send().await
+		.map_err(|e| e.to_string())?;
+	
+	let body = response.bytes().await
+		.map_err(|e| e.to_string())?;
+	
+	ScrapeResponse::from_bytes(&body)
+}
+
+
+// How many idle connections to keep open to each tracker, and for how long, so that
+// regular announces don't each need a fresh connection (and TLS handshake).
+const POOL_MAX_IDLE_PER_HOST: usize    = 4;
+const POOL_IDLE_TIMEOUT:      Duration = Duration::from_secs(90);
+const TCP_KEEPALIVE:          Duration = Duration::from_secs(60);
+
+// Owns a single HTTP client, configured from the network settings, through which every
+// announce and scrape is made, so that connections to trackers are reused.
+pub struct TrackerClient {
+	client: Client,
+	network_settings: NetworkSettings,
+}
+
+impl TrackerClient {
+	pub fn new(network_settings: NetworkSettings) -> Result<TrackerClient, reqwest::Error> {
+		TrackerClient::build(network_settings, false)
+	}
+	
+	// Speaks HTTP/2 from the start, rather than negotiating it. Only for trackers known to
+	// support HTTP/2, as plain HTTP/1 trackers will reject every request.
+	pub fn with_http2_prior_knowledge(network_settings: NetworkSettings) -> Result<TrackerClient, reqwest::Error> {
+		TrackerClient::build(network_settings, true)
+	}
+	
+	fn build(network_settings: NetworkSettings, http2_prior_knowledge: bool) -> Result<TrackerClient, reqwest::Error> {
+		let mut builder = network_settings.client_builder()
+			.pool_max_idle_per_host(POOL_MAX_IDLE_PER_HOST)
+			.pool_idle_timeout(POOL_IDLE_TIMEOUT)
+			.tcp_keepalive(TCP_KEEPALIVE);
+		
+		if http2_prior_knowledge {
+			builder = builder.http2_prior_knowledge();
+		}
+		
+		Ok(TrackerClient {
+			client: builder.build()?,
+			network_settings,
+		})
+	}
+	
+	pub fn client(&self) -> &Client {
+		&self.client
 	}
 	
-	request.send().await
+	pub fn network_settings(&self) -> &NetworkSettings {
+		&self.network_settings
+	}
+	
+	pub async fn announce(&self, torrent: &BTorrent, event: Option<BAnnounceEvent>) -> Result<BTrackerResponse, AnnounceError> {
+		announce_any(&self.client, torrent, event, &self.network_settings).await
+	}
+	
+	pub async fn scrape(&self, info_hashes: &[&[u8]], announce_url: &str) -> Result<ScrapeResponse, String> {
+		scrape_many(&self.client, info_hashes, announce_url).await
+	}
+}
+
+
+#[derive(Debug)]
+pub struct ScrapeResponse {
+	// Stats for each scraped torrent, keyed by info hash.
+	pub files: BTreeMap<Vec<u8>, ScrapeEntry>,
+}
+
+impl ScrapeResponse {
+	pub fn from_bytes(bytes: &[u8]) -> Result<ScrapeResponse, String> {
+		if nesting_depth(bytes) > MAX_NESTING_DEPTH {
+			return Err(String::from("Tracker response is too deeply nested."));
+		}
+		
+		let mut decoder = Decoder::new(bytes);
+		
+		// Read in and then parse the scrape response dictionary
+		let scrape_response = decoder.next_object()
+			.map_err(|x| x.to_string())?
+			.ok_or_else(|| String::from("Tracker sent empty response."))?;
+		let scrape_response = ScrapeResponse::decode_bencode_object(scrape_response)
+			.map_err(|x| x.to_string());
+		
+		// Ensure we've hit EOF
+		if decoder.next_object().map_err(|x| x.to_string())?.is_some() {
+			return Err(String::from("Erroneous data at the end of the scrape response."))
+		}
+		
+		scrape_response
+	}
+}
+
+impl FromBencode for ScrapeResponse {
+	fn decode_bencode_object(object: Object) -> Result<Self, DecodingError> {
+		let mut files = None;
+		
+		let mut dict = object.try_into_dictionary()?;
+		while let Some(keyval) = dict.next_pair()? {
+			match keyval {
+				(b"files", val) => {
+					let mut stats = BTreeMap::new();
+					
+					let mut files_dict = val.try_into_dictionary().context("files")?;
+					while let Some((info_hash, val)) = files_dict.next_pair()? {
+						let s = ScrapeEntry::decode_bencode_object(val)
+							.context("files")?;
+						
+						stats.insert(info_hash.to_vec(), s);
+					}
+					
+					files = Some(stats);
+				}
+				(key, _) => {
+					return Err(DecodingError::unexpected_field(String::from_utf8_lossy(key)));
+				}
+			}
+		}
+		
+		let files = files.ok_or_else(|| DecodingError::missing_field("files"))?;
+		
+		Ok(ScrapeResponse {
+			files,
+		})
+	}
+}
+
+
+// As with announce responses, some trackers leave fields out (most often `downloaded`), so
+// each is optional.
+#[derive(Debug, PartialEq)]
+pub struct ScrapeEntry {
+	pub complete: Option<u64>,   // number of seeders
+	pub downloaded: Option<u64>, // number of times the torrent has been completed
+	pub incomplete: Option<u64>, // number of leechers
 }
 
+impl FromBencode for ScrapeEntry {
+	fn decode_bencode_object(object: Object) -> Result<Self, DecodingError> {
+		let mut complete   = None;
+		let mut downloaded = None;
+		let mut incomplete = None;
+		
+		let mut dict = object.try_into_dictionary()?;
+		while let Some(keyval) = dict.next_pair()? {
+			match keyval {
+				(b"complete", val) => {
+					complete = u64::decode_bencode_object(val)
+						.context("complete")
+						.map(Some)?;
+				}
+				(b"downloaded", val) => {
+					downloaded = u64::decode_bencode_object(val)
+						.context("downloaded")
+						.map(Some)?;
+				}
+				(b"incomplete", val) => {
+					incomplete = u64::decode_bencode_object(val)
+						.context("incomplete")
+						.map(Some)?;
+				}
+				(key, _) => {
+					return Err(DecodingError::unexpected_field(String::from_utf8_lossy(key)));
+				}
+			}
+		}
+		
+		Ok(ScrapeEntry {
+			complete,
+			downloaded,
+			incomplete,
+		})
+	}
+}
+
+
+// The most peers we'll keep from a single tracker response. Far more than we could ever
+// connect to, but bounds the memory a malicious tracker can make us allocate.
+pub const DEFAULT_MAX_PEERS: usize = 10_000;
+
 
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BTrackerResponse {
-	peers: Vec<BPeer>,
-	interval: u64, // suggested minimum announce interval, in seconds
-	complete: Option<u64>,
-	incomplete: Option<u64>,
+	pub peers: Vec<BPeer>,
+	pub interval: u64, // suggested minimum announce interval, in seconds
+	pub complete: Option<u64>,
+	pub incomplete: Option<u64>,
+	
+	// Whether `peers` was sent in the compact format (BEP 23), as we ask for.
+	// Some trackers ignore `compact=1` and send a list of dictionaries anyway.
+	pub compact: bool,
+	
+	// Whether the tracker sent more peers than the parse limit, and the rest were dropped.
+	pub truncated: bool,
+	
+	// A `warning message` sent with an otherwise successful response.
+	pub warning: Option<String>,
+}
+
+// A one-line summary for logs, e.g., `interval=1800s, seeders=12, leechers=3, peers=15`.
+// Unknown counts are `?`. Failures aren't responses, so are `AnnounceError`s instead.
+impl fmt::Display for BTrackerResponse {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let count = |c: Option<u64>| c.map(|c| c.to_string()).unwrap_or_else(|| String::from("?"));
+		
+		write!(f, "interval={}s, seeders={}, leechers={}, peers={}",
+			self.interval,
+			count(self.complete),
+			count(self.incomplete),
+			self.peers.len(),
+		)?;
+		
+		if let Some(warning) = &self.warning {
+			write!(f, ", warning: {}", warning)?;
+		}
+		
+		Ok(())
+	}
 }
 
 impl BTrackerResponse {
 	pub fn from_bytes(bytes: &[u8]) -> Result<BTrackerResponse, String> {
-		let mut decoder = Decoder::new(&bytes);
+		BTrackerResponse::from_bytes_with_limit(bytes, DEFAULT_MAX_PEERS)
+	}
+	
+	// Like `from_bytes`, but keeps at most `max_peers` peers, setting `truncated` if there
+	// were more. Peers past the limit are never allocated.
+	pub fn from_bytes_with_limit(bytes: &[u8], max_peers: usize) -> Result<BTrackerResponse, String> {
+		if nesting_depth(bytes) > MAX_NESTING_DEPTH {
+			return Err(String::from("Tracker response is too deeply nested."));
+		}
+		
+		let mut decoder = Decoder::new(bytes);
 		
 		// Read in and then parse the tracker response dictionary
 		let tracker_response = decoder.next_object()
 			.map_err(|x| x.to_string())?
 			.ok_or_else(|| String::from("Tracker sent empty response."))?;
-		let tracker_response = BTrackerResponse::decode_bencode_object(tracker_response)
+		let tracker_response = BTrackerResponse::decode_with_limit(tracker_response, max_peers)
 			.map_err(|x| x.to_string());
 		
 		// Ensure we've hit EOF
@@ -92,30 +791,184 @@ impl BTrackerResponse {
 		tracker_response
 	}
 	
+	// Like `from_bytes`, but distinguishes a missing or cut-off body from an invalid one,
+	// since those are usually network problems rather than a misbehaving tracker.
+	pub fn from_announce_body(bytes: &[u8]) -> Result<BTrackerResponse, AnnounceError> {
+		if bytes.iter().all(u8::is_ascii_whitespace) {
+			return Err(AnnounceError::EmptyResponse);
+		}
+		
+		if is_truncated_bencode(bytes) {
+			return Err(AnnounceError::TruncatedResponse);
+		}
+		
+		if let Ok(failure) = TrackerFailure::from_bencode(bytes) {
+			return Err(AnnounceError::TrackerError(failure));
+		}
+		
+		BTrackerResponse::from_bytes(bytes).map_err(AnnounceError::InvalidResponse)
+	}
+	
+	// Parses the body of a UDP announce response (everything after the action and transaction
+	// id). Peers are IPv6 if the announce was made over IPv6.
+	pub fn from_udp_announce_body(bytes: &[u8], ipv6: bool) -> Result<BTrackerResponse, AnnounceError> {
+		if bytes.len() < 12 {
+			return Err(AnnounceError::TruncatedResponse);
+		}
+		
+		let interval   = u32::from_be_bytes(<[u8; 4]>::try_from( &bytes[0..4]).unwrap());
+		let incomplete = u32::from_be_bytes(<[u8; 4]>::try_from( &bytes[4..8]).unwrap());
+		let complete   = u32::from_be_bytes(<[u8; 4]>::try_from(&bytes[8..12]).unwrap());
+		
+		let (peers, truncated) = if ipv6 {
+			parse_compact_ipv6_peer_list(&bytes[12..], DEFAULT_MAX_PEERS)
+		} else {
+			parse_compact_ipv4_peer_list(&bytes[12..], DEFAULT_MAX_PEERS)
+		}.map_err(|e| AnnounceError::InvalidResponse(e.to_string()))?;
+		
+		Ok(BTrackerResponse {
+			peers,
+			interval:   u64::from(interval),
+			complete:   Some(u64::from(complete)),
+			incomplete: Some(u64::from(incomplete)),
+			compact:    true,
+			truncated,
+			warning:    None,
+		})
+	}
+	
+	// Drops any peers not reachable over the given address family.
+	pub fn retain_address_family(&mut self, address_family: AddressFamily) {
+		self.peers.retain(|p| address_family.allows(&p.ip));
+	}
+	
+	// Splits the peers into those reachable over IPv4 and those over IPv6, in that order.
+	pub fn peers_by_family(&self) -> (Vec<&BPeer>, Vec<&BPeer>) {
+		self.peers.iter().partition(|p| p.ip.is_ipv4())
+	}
+	
+	// Number of seeders in the swarm, as reported by the tracker.
+	pub fn seeders(&self) -> Option<u32> {
+		self.complete.map(saturate_u32)
+	}
+	
+	// Number of leechers in the swarm. If the tracker didn't report it, but did report the
+	// number of seeders, this is approximated as the peers we were sent that aren't seeders,
+	// which undercounts when the tracker sends only a subset of the swarm.
+	pub fn leechers(&self) -> Option<u32> {
+		match (self.incomplete, self.complete) {
+			(Some(incomplete), _) => Some(saturate_u32(incomplete)),
+			(None, Some(complete)) => Some(saturate_u32((self.peers.len() as u64).saturating_sub(complete))),
+			(None, None) => None,
+		}
+	}
+	
+	// Whether the tracker knows of nobody else in the swarm, as opposed to failing to respond.
+	pub fn is_empty_swarm(&self) -> bool {
+		self.peers.is_empty()
+			&& self.complete.unwrap_or(0) == 0
+			&& self.incomplete.unwrap_or(0) == 0
+	}
+	
 	// pub async fn from_response(response: reqwest::Response) -> Result<BTrackerResponse, String> {
 	// 	let bytes = response.bytes().await.map_err(|e| e.to_string())?;
 	// 	BTrackerResponse::from_bytes(&bytes)
 	// }
 }
 
+// Scans the structure of a bencoded value, without decoding it, to check whether the input
+// ends before the first value is complete. Malformed (but not truncated) input returns false.
+fn is_truncated_bencode(bytes: &[u8]) -> bool {
+	let mut depth = 0;
+	let mut i     = 0;
+	
+	loop {
+		let token = match bytes.get(i) {
+			Some(token) => *token,
+			None => return true,
+		};
+		
+		match token {
+			b'd' | b'l' => {
+				depth += 1;
+				i += 1;
+			}
+			b'e' => {
+				if depth == 0 {
+					return false;
+				}
+				
+				depth -= 1;
+				i += 1;
+			}
+			b'i' => {
+				match bytes[i..].iter().position(|&b| b == b'e') {
+					Some(end) => i += end + 1,
+					None => return true,
+				}
+			}
+			b'0'..=b'9' => {
+				let colon = match bytes[i..].iter().position(|&b| b == b':') {
+					Some(colon) => i + colon,
+					None => return true,
+				};
+				
+				let len = match std::str::from_utf8(&bytes[i..colon]).ok().and_then(|l| l.parse::<usize>().ok()) {
+					Some(len) => len,
+					None => return false,
+				};
+				
+				i = match (colon + 1).checked_add(len) {
+					Some(end) if end <= bytes.len() => end,
+					_ => return true,
+				};
+			}
+			_ => return false,
+		}
+		
+		if depth == 0 {
+			return false;
+		}
+	}
+}
+
+// Decodes an integer, which some trackers send as a string of digits (e.g., `4:1800`).
+fn decode_lenient_u64(object: Object) -> Result<u64, DecodingError> {
+	match object {
+		Object::Bytes(bytes) => std::str::from_utf8(bytes).ok()
+			.and_then(|s| s.parse::<u64>().ok())
+			.ok_or_else(|| DecodingError::malformed_content(err_msg("expected an integer or a string of digits"))),
+		object => u64::decode_bencode_object(object),
+	}
+}
+
+fn saturate_u32(x: u64) -> u32 {
+	u32::try_from(x).unwrap_or(u32::MAX)
+}
+
 impl FromBencode for BTrackerResponse {
 	fn decode_bencode_object(object: Object) -> Result<Self, DecodingError> {
+		BTrackerResponse::decode_with_limit(object, DEFAULT_MAX_PEERS)
+	}
+}
+
+impl BTrackerResponse {
+	fn decode_with_limit(object: Object, max_peers: usize) -> Result<Self, DecodingError> {
 		let mut peers      = None;
 		let mut peers6     = None;
 		let mut interval   = None;
 		let mut complete   = None;
 		let mut incomplete = None;
+		let mut compact    = false;
+		let mut truncated  = false;
+		let mut warning    = None;
 		
 		let mut dict = object.try_into_dictionary()?;
 		while let Some(keyval) = dict.next_pair()? {
 			match keyval {
+				// Either form may be empty, meaning the tracker knows of no other peers yet.
 				(b"peers", val) => {
 					match val {
-						Object::List(_) => {
-							peers = Vec::decode_bencode_object(val)
-								.context("peers")
-								.map(Some)?;
-						}
 						Object::Bytes(_) => {
 							// `AsString` is a wrapper allowing us to decode/encode a Vec<u8>.
 							// It contains only one field -- the Vec<u8>. Unwrap it.
@@ -123,12 +976,23 @@ impl FromBencode for BTrackerResponse {
 								.context("peers")
 								.map(|b| b.0)?;
 							
-							peers = parse_compact_ipv4_peer_list(&peers_bytestring)
-								.map(Some)?;
+							let (list, t) = parse_compact_ipv4_peer_list(&peers_bytestring, max_peers)?;
+							
+							peers     = Some(list);
+							compact   = true;
+							truncated |= t;
+						}
+						Object::List(_) => {
+							let (list, t) = parse_peer_dict_list(val, max_peers)
+								.context("peers")?;
+							
+							peers     = Some(list);
+							compact   = false;
+							truncated |= t;
 						}
 						_ => {
 							return Err(DecodingError::malformed_content(
-								err_msg("peers key must be either a dictionary or a list")
+								err_msg("peers key must be either a byte string or a list")
 							));
 						}
 					}
@@ -140,30 +1004,39 @@ impl FromBencode for BTrackerResponse {
 						.context("peers6")
 						.map(|b| b.0)?;
 					
-					peers6 = parse_compact_ipv6_peer_list(&peers_bytestring)
-						.map(Some)?;
+					let (list, t) = parse_compact_ipv6_peer_list(&peers_bytestring, max_peers)?;
+					
+					peers6    = Some(list);
+					truncated |= t;
 				}
 				(b"interval", val) => {
-					interval = u64::decode_bencode_object(val)
+					interval = decode_lenient_u64(val)
 						.context("interval")
 						.map(Some)?;
 				}
 				(b"complete", val) => {
-					complete = u64::decode_bencode_object(val)
+					complete = decode_lenient_u64(val)
 						.context("complete")
 						.map(Some)?;
 				}
 				(b"incomplete", val) => {
-					incomplete = u64::decode_bencode_object(val)
+					incomplete = decode_lenient_u64(val)
 						.context("incomplete")
 						.map(Some)?;
 				}
+				(b"warning message", val) => {
+					warning = String::decode_bencode_object(val)
+						.context("warning message")
+						.map(Some)?;
+				}
 				(key, _) => {
 					return Err(DecodingError::unexpected_field(String::from_utf8_lossy(key)));
 				}
 			}
 		}
 		
+		// A response without `peers` is an error, even with `peers6`, as BEP 3 requires it.
+		// Trackers with no peers to give send an empty list rather than leaving it out.
 		let mut peers    =    peers.ok_or_else(|| DecodingError::missing_field("peers"   ))?;
 		let     interval = interval.ok_or_else(|| DecodingError::missing_field("interval"))?;
 		
@@ -173,20 +1046,54 @@ impl FromBencode for BTrackerResponse {
 			peers.append(&mut peers6);
 		}
 		
+		if peers.len() > max_peers {
+			peers.truncate(max_peers);
+			truncated = true;
+		}
+		
 		Ok(BTrackerResponse {
 			peers,
 			interval,
 			complete,
 			incomplete,
+			compact,
+			truncated,
+			warning,
 		})
 	}
 }
 
 
-struct BPeer {
-	ip: IpAddr,
-	peer_id: String,
-	port: u16,
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BPeer {
+	pub ip: IpAddr,
+	pub peer_id: String,
+	pub port: u16,
+}
+
+// Peers are equal if they have the same address. `peer_id` is ignored, since compact peer
+// lists don't include it, and the same peer may be returned in both forms.
+impl PartialEq for BPeer {
+	fn eq(&self, other: &BPeer) -> bool {
+		self.ip == other.ip && self.port == other.port
+	}
+}
+
+impl Eq for BPeer {}
+
+impl Hash for BPeer {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.ip.hash(state);
+		self.port.hash(state);
+	}
+}
+
+impl BPeer {
+	// The peer's address as `ip:port`, with IPv6 addresses bracketed, e.g., `[::1]:6881`.
+	pub fn connect_string(&self) -> String {
+		SocketAddr::new(self.ip, self.port).to_string()
+	}
 }
 
 impl FromBencode for BPeer {
@@ -202,11 +1109,14 @@ impl FromBencode for BPeer {
 					let ip_string = String::decode_bencode_object(val)
 						.context("ip")?;
 					
-					// Bloated peer list ip could either be Ipv4 or Ipv6
-					let ip_obj: IpAddr = ip_string.parse()
-						.map_err(|_| DecodingError::malformed_content(
-							err_msg("invalid ip address")
-						))?;
+					// Bloated peer list ip could either be Ipv4 or Ipv6. Some trackers put
+					// Ipv6 addresses in brackets, as in urls.
+					let ip_obj = match ip_string.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+						Some(ipv6) => ipv6.parse::<Ipv6Addr>().map(IpAddr::V6),
+						None       => ip_string.parse::<IpAddr>(),
+					}.map_err(|_| DecodingError::malformed_content(
+						err_msg("invalid ip address")
+					))?;
 					
 					ip = Some(ip_obj);
 				}
@@ -239,19 +1149,39 @@ impl FromBencode for BPeer {
 }
 
 
-fn parse_compact_ipv4_peer_list(bytes: &[u8]) -> Result<Vec<BPeer>, DecodingError> {
-	let mut peers = Vec::new();
+// Decodes a non-compact (BEP 3) peer list, keeping at most `max_peers` peers. Also returns
+// whether any were dropped.
+fn parse_peer_dict_list(object: Object, max_peers: usize) -> Result<(Vec<BPeer>, bool), DecodingError> {
+	let mut peers     = Vec::new();
+	let mut truncated = false;
+	
+	let mut list = object.try_into_list()?;
+	while let Some(peer) = list.next_object()? {
+		if peers.len() < max_peers {
+			peers.push(BPeer::decode_bencode_object(peer)?);
+		} else {
+			truncated = true;
+		}
+	}
+	
+	Ok((peers, truncated))
+}
+
+// The compact peer lists keep at most `max_peers` peers, and also return whether any were
+// dropped. The whole list must still be well-formed.
+fn parse_compact_ipv4_peer_list(bytes: &[u8], max_peers: usize) -> Result<(Vec<BPeer>, bool), DecodingError> {
+	let count     = bytes.len() / 6;
+	let mut peers = Vec::with_capacity(count.min(max_peers));
 	
-	if bytes.len() % 6 != 0 {
+	if !bytes.len().is_multiple_of(6) {
 		return Err(DecodingError::malformed_content(
 			err_msg("incomplete compact ipv4 peers list (length is not divisible by 6)")
 		));
 	}
 	
-	for i in bytes.chunks(6) {
-		// Give the slices compile-time sizes.
-		let ip   = <[u8; 4]>::try_from(&i[0..4]).unwrap();
-		let port = <[u8; 2]>::try_from(&i[4..6]).unwrap();
+	for i in bytes.chunks(6).take(max_peers) {
+		let ip   = compact_field::<4>(i, 0)?;
+		let port = compact_field::<2>(i, 4)?;
 		
 		let ip   = IpAddr::V4(Ipv4Addr::from(ip.map(u8::from_be)));
 		let port = u16::from_be_bytes(port);
@@ -263,22 +1193,22 @@ fn parse_compact_ipv4_peer_list(bytes: &[u8]) -> Result<Vec<BPeer>, DecodingErro
 		});
 	}
 	
-	Ok(peers)
+	Ok((peers, count > max_peers))
 }
 
-fn parse_compact_ipv6_peer_list(bytes: &[u8]) -> Result<Vec<BPeer>, DecodingError> {
-	let mut peers = Vec::new();
+fn parse_compact_ipv6_peer_list(bytes: &[u8], max_peers: usize) -> Result<(Vec<BPeer>, bool), DecodingError> {
+	let count     = bytes.len() / 18;
+	let mut peers = Vec::with_capacity(count.min(max_peers));
 	
-	if bytes.len() % 18 != 0 {
+	if !bytes.len().is_multiple_of(18) {
 		return Err(DecodingError::malformed_content(
-			err_msg("incomplete compact ipv4 peers list (length is not divisible by 18)")
+			err_msg("incomplete compact ipv6 peers list (length is not divisible by 18)")
 		));
 	}
 	
-	for i in bytes.chunks(18) {
-		// Give the slices compile-time sizes.
-		let ip   = <[u8; 16]>::try_from( &i[0..16]).unwrap();
-		let port = <[u8;  2]>::try_from(&i[16..18]).unwrap();
+	for i in bytes.chunks(18).take(max_peers) {
+		let ip   = compact_field::<16>(i, 0)?;
+		let port = compact_field::<2>(i, 16)?;
 		
 		let ip   = IpAddr::V6(Ipv6Addr::from(ip.map(u8::from_be)));
 		let port = u16::from_be_bytes(port);
@@ -290,5 +1220,818 @@ fn parse_compact_ipv6_peer_list(bytes: &[u8]) -> Result<Vec<BPeer>, DecodingErro
 		});
 	}
 	
-	Ok(peers)
+	Ok((peers, count > max_peers))
+}
+
+// Reads a fixed-size field from an entry in a compact peer list. The length checks above
+// mean this shouldn't fail, but tracker responses are untrusted, so we never index blindly.
+fn compact_field<const N: usize>(entry: &[u8], start: usize) -> Result<[u8; N], DecodingError> {
+	entry.get(start..start + N)
+		.and_then(|field| <[u8; N]>::try_from(field).ok())
+		.ok_or_else(|| DecodingError::malformed_content(
+			err_msg("truncated entry in compact peers list")
+		))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	use crate::metainfo::BMetainfo;
+	
+	fn test_torrent() -> BTorrent {
+		let metainfo = BMetainfo::from_path("test_torrents/single_file.torrent").unwrap();
+		BTorrent::new(metainfo).unwrap()
+	}
+	
+	fn test_network_settings() -> NetworkSettings {
+		NetworkSettings {
+			port: 6000,
+			..NetworkSettings::default()
+		}
+	}
+	
+	fn query_pairs(request: RequestBuilder) -> Vec<(String, String)> {
+		request.build().unwrap()
+			.url()
+			.query_pairs()
+			.map(|(k, v)| (k.into_owned(), v.into_owned()))
+			.collect()
+	}
+	
+	#[test]
+	fn test_scrape_url() {
+		assert_eq!(scrape_url("http://example.com/announce").as_deref(), Some("http://example.com/scrape"));
+		assert_eq!(scrape_url("http://example.com/x/announce.php?pk=1").as_deref(), Some("http://example.com/x/scrape.php?pk=1"));
+		assert_eq!(scrape_url("http://example.com/a"), None);
+		assert_eq!(scrape_url("http://example.com/announce/x"), None);
+	}
+	
+	#[test]
+	fn test_scrape_entry_missing_downloaded() {
+		let mut body = Vec::new();
+		body.extend_from_slice(b"d5:filesd20:");
+		body.extend_from_slice(&[b'a'; 20]);
+		body.extend_from_slice(b"d8:completei5e10:incompletei10eeee");
+		
+		let response = ScrapeResponse::from_bytes(&body).unwrap();
+		assert_eq!(response.files[&[b'a'; 20][..]], ScrapeEntry { complete: Some(5), downloaded: None, incomplete: Some(10) });
+	}
+	
+	#[tokio::test]
+	async fn test_scrape_many() {
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+		use wiremock::matchers::{method, path};
+		
+		let hash_a = [b'a'; 20];
+		let hash_b = [b'b'; 20];
+		
+		let mut body = Vec::new();
+		body.extend_from_slice(b"d5:filesd20:");
+		body.extend_from_slice(&hash_a);
+		body.extend_from_slice(b"d8:completei5e10:downloadedi50e10:incompletei10ee20:");
+		body.extend_from_slice(&hash_b);
+		body.extend_from_slice(b"d8:completei1e10:downloadedi2e10:incompletei3eeee");
+		
+		let server = MockServer::start().await;
+		Mock::given(method("GET"))
+			.and(path("/scrape"))
+			.respond_with(ResponseTemplate::new(200).set_body_bytes(body))
+			.mount(&server)
+			.await;
+		
+		let client = Client::new();
+		let announce_url = format!("{}/announce", server.uri());
+		let response = scrape_many(&client, &[&hash_a, &hash_b], &announce_url).await.unwrap();
+		
+		assert_eq!(response.files.len(), 2);
+		assert_eq!(response.files[&hash_a[..]], ScrapeEntry { complete: Some(5), downloaded: Some(50), incomplete: Some(10) });
+		assert_eq!(response.files[&hash_b[..]], ScrapeEntry { complete: Some(1), downloaded: Some(2), incomplete: Some(3) });
+		
+		// Both hashes were sent in the one request.
+		let requests = server.received_requests().await.unwrap();
+		assert_eq!(requests.len(), 1);
+		assert_eq!(requests[0].url.query_pairs().filter(|(k, _)| k == "info_hash").count(), 2);
+	}
+	
+	#[tokio::test]
+	async fn test_announce_all_tiers() {
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+		use wiremock::matchers::{method, path};
+		use crate::metainfo::BMetainfoBuilder;
+		
+		let server = MockServer::start().await;
+		Mock::given(method("GET"))
+			.and(path("/rejects"))
+			.respond_with(ResponseTemplate::new(200).set_body_bytes(&b"d14:failure reason22:torrent not registerede"[..]))
+			.mount(&server)
+			.await;
+		Mock::given(method("GET"))
+			.and(path("/accepts"))
+			.respond_with(ResponseTemplate::new(200).set_body_bytes(&b"d8:intervali1800e5:peers6:\x7f\x00\x00\x01\x1a\xe1e"[..]))
+			.mount(&server)
+			.await;
+		
+		let rejects = format!("{}/rejects", server.uri());
+		let accepts = format!("{}/accepts", server.uri());
+		let unreachable = String::from("http://127.0.0.1:1/announce");
+		
+		let torrent = |tier: Vec<String>| {
+			let metainfo = BMetainfoBuilder::new()
+				.announce(&tier[0])
+				.add_tracker_tier(tier)
+				.name("a")
+				.piece_length(16384)
+				.pieces(vec![0; 20])
+				.length(1)
+				.build()
+				.unwrap();
+			BTorrent::new(metainfo).unwrap()
+		};
+		
+		let client = Client::new();
+		let ns = test_network_settings();
+		
+		let (url, response) = announce_all_tiers(&client, &torrent(vec![rejects.clone(), accepts.clone()]), None, &ns).await.unwrap();
+		assert_eq!(url, accepts);
+		assert_eq!(response.peers.len(), 1);
+		
+		// Every failure is collected, whether from the tracker or the network.
+		let failures = announce_all_tiers(&client, &torrent(vec![rejects.clone(), unreachable.clone()]), None, &ns).await.unwrap_err();
+		assert_eq!(failures.len(), 2);
+		assert!(matches!(&failures[0], (url, AnnounceError::TrackerError(f)) if *url == rejects && f.reason == "torrent not registered"));
+		assert!(matches!(&failures[1], (url, AnnounceError::Http(_)) if *url == unreachable));
+	}
+	
+	#[tokio::test]
+	async fn test_announce_gather() {
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+		use wiremock::matchers::{method, path};
+		use crate::metainfo::BMetainfoBuilder;
+		
+		let server = MockServer::start().await;
+		Mock::given(method("GET"))
+			.and(path("/rejects"))
+			.respond_with(ResponseTemplate::new(200).set_body_bytes(&b"d14:failure reason22:torrent not registerede"[..]))
+			.mount(&server)
+			.await;
+		Mock::given(method("GET"))
+			.and(path("/one"))
+			.respond_with(ResponseTemplate::new(200).set_body_bytes(&b"d8:intervali1800e5:peers6:\x7f\x00\x00\x01\x1a\xe1e"[..]))
+			.mount(&server)
+			.await;
+		Mock::given(method("GET"))
+			.and(path("/two"))
+			.respond_with(ResponseTemplate::new(200).set_body_bytes(&b"d8:intervali1800e5:peers12:\x7f\x00\x00\x01\x1a\xe1\x7f\x00\x00\x02\x1a\xe1e"[..]))
+			.mount(&server)
+			.await;
+		
+		let rejects = format!("{}/rejects", server.uri());
+		let one     = format!("{}/one", server.uri());
+		let two     = format!("{}/two", server.uri());
+		
+		let metainfo = BMetainfoBuilder::new()
+			.announce(&rejects)
+			.add_tracker_tier(vec![rejects.clone(), two.clone()])
+			.add_tracker_tier(vec![one.clone()])
+			.name("a")
+			.piece_length(16384)
+			.pieces(vec![0; 20])
+			.length(1)
+			.build()
+			.unwrap();
+		let torrent = BTorrent::new(metainfo).unwrap();
+		
+		let gathered = announce_gather(&Client::new(), &torrent, None, &test_network_settings()).await.unwrap();
+		assert_eq!(gathered.responded_tracker, two);
+		assert_eq!(gathered.peer_counts, vec![(two, 2), (one, 1)]);
+		assert_eq!(gathered.peers.len(), 2);
+		assert_eq!(gathered.failures.len(), 1);
+		assert_eq!(gathered.failures[0].0, rejects);
+	}
+	
+	#[tokio::test]
+	async fn test_announce_address_family() {
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+		use wiremock::matchers::{method, path};
+		
+		let body = b"d8:intervali1800e5:peers6:\x7f\x00\x00\x01\x1a\xe16:peers618:\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x1a\xe1e";
+		
+		let server = MockServer::start().await;
+		Mock::given(method("GET"))
+			.and(path("/announce"))
+			.respond_with(ResponseTemplate::new(200).set_body_bytes(&body[..]))
+			.mount(&server)
+			.await;
+		
+		let mut torrent = test_torrent();
+		torrent.announce = format!("{}/announce", server.uri());
+		
+		let mut ns = test_network_settings();
+		let client = ns.build_client().unwrap();
+		let response = announce(&client, &torrent, None, &ns).await.unwrap();
+		assert_eq!(response.peers.len(), 2);
+		
+		ns.address_family = AddressFamily::V4;
+		let client = ns.build_client().unwrap();
+		let response = announce(&client, &torrent, None, &ns).await.unwrap();
+		assert_eq!(response.peers.len(), 1);
+		assert!(response.peers[0].ip.is_ipv4());
+	}
+	
+	#[tokio::test]
+	async fn test_announce_any_http() {
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+		use wiremock::matchers::{method, path};
+		
+		let body = b"d8:intervali1800e5:peers6:\x7f\x00\x00\x01\x1a\xe1e";
+		
+		let server = MockServer::start().await;
+		Mock::given(method("GET"))
+			.and(path("/announce"))
+			.respond_with(ResponseTemplate::new(200).set_body_bytes(&body[..]))
+			.mount(&server)
+			.await;
+		
+		let mut torrent = test_torrent();
+		torrent.announce = format!("{}/announce", server.uri());
+		
+		let ns = test_network_settings();
+		let client = ns.build_client().unwrap();
+		let response = announce_any(&client, &torrent, Some(BAnnounceEvent::Started), &ns).await.unwrap();
+		
+		assert_eq!(response.interval, 1800);
+		assert_eq!(response.peers.len(), 1);
+		assert_eq!(response.peers[0].port, 6881);
+	}
+	
+	#[tokio::test]
+	async fn test_announce_any_udp() {
+		let tracker = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+		let tracker_addr = tracker.local_addr().unwrap();
+		
+		let mut torrent = test_torrent();
+		torrent.announce = format!("udp://{}/announce", tracker_addr);
+		torrent.numwant  = Some(100);
+		let info_hash = torrent.info_hash.clone();
+		
+		// Plays the tracker's side of one connect and one announce exchange.
+		let mock = tokio::spawn(async move {
+			let connection_id = 0x1234_5678_9abc_def0u64;
+			let mut buf = [0; 1024];
+			
+			let (len, peer) = tracker.recv_from(&mut buf).await.unwrap();
+			assert_eq!(len, 16);
+			assert_eq!(buf[0..8], UDP_PROTOCOL_ID.to_be_bytes());
+			assert_eq!(buf[8..12], UDP_ACTION_CONNECT.to_be_bytes());
+			
+			let mut reply = Vec::new();
+			reply.extend_from_slice(&UDP_ACTION_CONNECT.to_be_bytes());
+			reply.extend_from_slice(&buf[12..16]);
+			reply.extend_from_slice(&connection_id.to_be_bytes());
+			tracker.send_to(&reply, peer).await.unwrap();
+			
+			let (len, peer) = tracker.recv_from(&mut buf).await.unwrap();
+			assert_eq!(len, 98);
+			assert_eq!(buf[0..8], connection_id.to_be_bytes());
+			assert_eq!(buf[8..12], UDP_ACTION_ANNOUNCE.to_be_bytes());
+			assert_eq!(buf[16..36], info_hash[..]);
+			assert_eq!(buf[80..84], 2u32.to_be_bytes()); // started
+			assert_eq!(buf[92..96], 100i32.to_be_bytes());
+			assert_eq!(buf[96..98], 6000u16.to_be_bytes());
+			
+			let mut reply = Vec::new();
+			reply.extend_from_slice(&UDP_ACTION_ANNOUNCE.to_be_bytes());
+			reply.extend_from_slice(&buf[12..16]);
+			reply.extend_from_slice(&1800u32.to_be_bytes()); // interval
+			reply.extend_from_slice(&3u32.to_be_bytes());    // leechers
+			reply.extend_from_slice(&5u32.to_be_bytes());    // seeders
+			reply.extend_from_slice(&[127, 0, 0, 1, 0x1a, 0xe1]);
+			reply.extend_from_slice(&[10, 0, 0, 2, 0x1a, 0xe2]);
+			tracker.send_to(&reply, peer).await.unwrap();
+		});
+		
+		let ns = test_network_settings();
+		let client = ns.build_client().unwrap();
+		let response = announce_any(&client, &torrent, Some(BAnnounceEvent::Started), &ns).await.unwrap();
+		mock.await.unwrap();
+		
+		assert_eq!(response.interval, 1800);
+		assert_eq!(response.seeders(), Some(5));
+		assert_eq!(response.leechers(), Some(3));
+		assert_eq!(response.peers.len(), 2);
+		assert_eq!(response.peers[1].ip, "10.0.0.2".parse::<IpAddr>().unwrap());
+		assert_eq!(response.peers[1].port, 6882);
+	}
+	
+	#[tokio::test]
+	async fn test_tracker_client() {
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+		use wiremock::matchers::{method, path};
+		
+		let body = b"d8:intervali1800e5:peers6:\x7f\x00\x00\x01\x1a\xe1e";
+		
+		let server = MockServer::start().await;
+		Mock::given(method("GET"))
+			.and(path("/announce"))
+			.respond_with(ResponseTemplate::new(200).set_body_bytes(&body[..]))
+			.expect(2)
+			.mount(&server)
+			.await;
+		
+		let mut torrent = test_torrent();
+		torrent.announce = format!("{}/announce", server.uri());
+		
+		let client = TrackerClient::new(test_network_settings()).unwrap();
+		
+		let first = client.announce(&torrent, Some(BAnnounceEvent::Started)).await.unwrap();
+		assert_eq!(first.peers.len(), 1);
+		
+		let second = client.announce(&torrent, None).await.unwrap();
+		assert_eq!(second.interval, 1800);
+		
+		server.verify().await;
+	}
+	
+	#[tokio::test]
+	async fn test_announce_extra_headers() {
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+		use wiremock::matchers::{header, method, path};
+		
+		let body = b"d8:intervali1800e5:peers0:e";
+		
+		let server = MockServer::start().await;
+		Mock::given(method("GET"))
+			.and(path("/announce"))
+			.and(header("Authorization", "Bearer secret"))
+			.respond_with(ResponseTemplate::new(200).set_body_bytes(&body[..]))
+			.mount(&server)
+			.await;
+		
+		let mut torrent = test_torrent();
+		torrent.announce = format!("{}/announce", server.uri());
+		
+		let mut ns = test_network_settings();
+		let client = ns.build_client().unwrap();
+		
+		// Without the header, the mock doesn't match, and so responds with a 404.
+		assert!(announce(&client, &torrent, None, &ns).await.is_err());
+		
+		ns.extra_headers.push((String::from("Authorization"), String::from("Bearer secret")));
+		let response = announce(&client, &torrent, None, &ns).await.unwrap();
+		assert_eq!(response.interval, 1800);
+	}
+	
+	#[tokio::test]
+	async fn test_announce_any_unsupported() {
+		let mut torrent = test_torrent();
+		torrent.announce = String::from("wss://tracker.example.com/announce");
+		
+		let ns = test_network_settings();
+		let client = ns.build_client().unwrap();
+		let result = announce_any(&client, &torrent, None, &ns).await;
+		
+		assert!(matches!(result, Err(AnnounceError::UnsupportedUrl(_))));
+	}
+	
+	#[test]
+	fn test_parse_large_compact_peer_lists() {
+		// 10,000 peers is 60 KB in compact IPv4 form.
+		let bytes: Vec<u8> = (0..10_000u32)
+			.flat_map(|i| {
+				let [a, b, c, d] = i.to_be_bytes();
+				vec![a, b, c, d, 0x1a, 0xe1]
+			})
+			.collect();
+		assert_eq!(bytes.len(), 60_000);
+		
+		let (peers, truncated) = parse_compact_ipv4_peer_list(&bytes, DEFAULT_MAX_PEERS).unwrap();
+		
+		// An exact capacity means the list was allocated once, up front, rather than grown.
+		assert!(!truncated);
+		assert_eq!(peers.len(), 10_000);
+		assert_eq!(peers.capacity(), 10_000);
+		assert_eq!(peers[9_999].ip, IpAddr::V4(Ipv4Addr::from(9_999u32)));
+		
+		let bytes = vec![0; 18 * 10_000];
+		let (peers, _) = parse_compact_ipv6_peer_list(&bytes, DEFAULT_MAX_PEERS).unwrap();
+		
+		assert_eq!(peers.len(), 10_000);
+		assert_eq!(peers.capacity(), 10_000);
+	}
+	
+	#[test]
+	fn test_too_deeply_nested() {
+		let mut bytes = b"d8:intervali1800e5:peers".to_vec();
+		bytes.extend(vec![b'l'; 100_000]);
+		bytes.extend(vec![b'e'; 100_000]);
+		bytes.push(b'e');
+		
+		assert!(BTrackerResponse::from_bytes(&bytes).is_err());
+		assert!(ScrapeResponse::from_bytes(&bytes).is_err());
+	}
+	
+	#[test]
+	fn test_display() {
+		let response = BTrackerResponse::from_bytes(b"d8:completei12e10:incompletei3e8:intervali1800e5:peers12:\x7f\x00\x00\x01\x1a\xe1\x7f\x00\x00\x02\x1a\xe1e").unwrap();
+		assert_eq!(response.to_string(), "interval=1800s, seeders=12, leechers=3, peers=2");
+		
+		let response = BTrackerResponse::from_bytes(b"d8:intervali900e5:peers0:15:warning message12:ratio is lowe").unwrap();
+		assert_eq!(response.to_string(), "interval=900s, seeders=?, leechers=?, peers=0, warning: ratio is low");
+	}
+	
+	#[test]
+	fn test_peer_dict_bracketed_ipv6() {
+		let body = b"d8:intervali1800e5:peersld2:ip13:[2001:db8::1]7:peer id1:a4:porti6881eeee";
+		let response = BTrackerResponse::from_bytes(body).unwrap();
+		assert_eq!(response.peers[0].ip, "2001:db8::1".parse::<IpAddr>().unwrap());
+		
+		// Brackets are only for Ipv6, and must be balanced.
+		assert!(BTrackerResponse::from_bytes(b"d8:intervali1800e5:peersld2:ip9:[1.2.3.4]7:peer id1:a4:porti6881eeee").is_err());
+		assert!(BTrackerResponse::from_bytes(b"d8:intervali1800e5:peersld2:ip12:[2001:db8::17:peer id1:a4:porti6881eeee").is_err());
+	}
+	
+	#[test]
+	fn test_integers_as_strings() {
+		let response = BTrackerResponse::from_bytes(b"d8:complete2:1210:incomplete1:38:interval4:18005:peers0:e").unwrap();
+		assert_eq!(response.interval, 1800);
+		assert_eq!(response.complete, Some(12));
+		assert_eq!(response.incomplete, Some(3));
+		
+		assert!(BTrackerResponse::from_bytes(b"d8:interval4:soon5:peers0:e").is_err());
+		assert!(BTrackerResponse::from_bytes(b"d8:interval2:-15:peers0:e").is_err());
+	}
+	
+	#[test]
+	fn test_empty_peers() {
+		let response = BTrackerResponse::from_bytes(b"d8:intervali1800e5:peers0:e").unwrap();
+		assert!(response.peers.is_empty());
+		assert!(response.compact);
+		
+		let response = BTrackerResponse::from_bytes(b"d8:intervali1800e5:peerslee").unwrap();
+		assert!(response.peers.is_empty());
+		assert!(!response.compact);
+		
+		assert!(BTrackerResponse::from_bytes(b"d8:intervali1800ee").is_err());
+		assert!(BTrackerResponse::from_bytes(b"d8:intervali1800e6:peers60:e").is_err());
+	}
+	
+	#[test]
+	fn test_peer_limit() {
+		// 20,000 compact peers, twice the default limit.
+		let peers: Vec<u8> = (0..20_000u32)
+			.flat_map(|i| {
+				let [a, b, c, d] = i.to_be_bytes();
+				vec![a, b, c, d, 0x1a, 0xe1]
+			})
+			.collect();
+		let mut bytes = format!("d8:intervali1800e5:peers{}:", peers.len()).into_bytes();
+		bytes.extend_from_slice(&peers);
+		bytes.push(b'e');
+		
+		let response = BTrackerResponse::from_bytes(&bytes).unwrap();
+		assert!(response.truncated);
+		assert_eq!(response.peers.len(), DEFAULT_MAX_PEERS);
+		assert_eq!(response.peers.capacity(), DEFAULT_MAX_PEERS);
+		assert_eq!(response.peers[9_999].ip, IpAddr::V4(Ipv4Addr::from(9_999u32)));
+		
+		let response = BTrackerResponse::from_bytes_with_limit(&bytes, 20_000).unwrap();
+		assert!(!response.truncated);
+		assert_eq!(response.peers.len(), 20_000);
+		
+		// The limit covers IPv4 and IPv6 peers together, and non-compact lists.
+		let bytes = b"d8:intervali1800e5:peers12:\x01\x02\x03\x04\x1a\xe1\x05\x06\x07\x08\x1a\xe16:peers618:\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\x01\x1a\xe1e";
+		let response = BTrackerResponse::from_bytes_with_limit(bytes, 2).unwrap();
+		assert!(response.truncated);
+		assert_eq!(response.peers.len(), 2);
+		assert!(response.peers.iter().all(|p| p.ip.is_ipv4()));
+		
+		let bytes = b"d8:intervali1800e5:peersld2:ip7:1.2.3.47:peer id1:a4:porti6881eed2:ip7:5.6.7.87:peer id1:b4:porti6881eeee";
+		let response = BTrackerResponse::from_bytes_with_limit(bytes, 1).unwrap();
+		assert!(response.truncated);
+		assert_eq!(response.peers[0].ip, "1.2.3.4".parse::<IpAddr>().unwrap());
+		assert!(!BTrackerResponse::from_bytes(bytes).unwrap().truncated);
+	}
+	
+	proptest::proptest! {
+		#[test]
+		fn test_compact_peer_lists_never_panic(bytes in proptest::collection::vec(proptest::num::u8::ANY, 0..256)) {
+			let v4 = parse_compact_ipv4_peer_list(&bytes, usize::MAX);
+			let v6 = parse_compact_ipv6_peer_list(&bytes, usize::MAX);
+			
+			proptest::prop_assert_eq!(v4.is_ok(), bytes.len() % 6 == 0);
+			proptest::prop_assert_eq!(v6.is_ok(), bytes.len() % 18 == 0);
+			
+			if let Ok((peers, _)) = v4 {
+				proptest::prop_assert_eq!(peers.len(), bytes.len() / 6);
+			}
+		}
+	}
+	
+	#[test]
+	fn test_from_announce_body() {
+		assert!(matches!(
+			BTrackerResponse::from_announce_body(b""),
+			Err(AnnounceError::EmptyResponse)
+		));
+		
+		assert!(matches!(
+			BTrackerResponse::from_announce_body(b"d8:intervali18"),
+			Err(AnnounceError::TruncatedResponse)
+		));
+		
+		assert!(matches!(
+			BTrackerResponse::from_announce_body(b"d8:intervali1800e5:peers6:\x7f\x00"),
+			Err(AnnounceError::TruncatedResponse)
+		));
+		
+		assert!(matches!(
+			BTrackerResponse::from_announce_body(b"d8:intervali1800e5:peers6:\x7f\x00\x00\x01\x1a\xe1"),
+			Err(AnnounceError::TruncatedResponse)
+		));
+		
+		assert!(matches!(
+			BTrackerResponse::from_announce_body(b"d3:fooi1ee"),
+			Err(AnnounceError::InvalidResponse(_))
+		));
+		
+		let response = BTrackerResponse::from_announce_body(
+			b"d8:intervali1800e5:peers6:\x7f\x00\x00\x01\x1a\xe1e"
+		).unwrap();
+		assert_eq!(response.interval, 1800);
+		assert_eq!(response.peers.len(), 1);
+	}
+	
+	#[test]
+	fn test_seeders_leechers() {
+		let response = BTrackerResponse::from_bytes(
+			b"d8:completei5e10:incompletei7e8:intervali1800e5:peers6:\x7f\x00\x00\x01\x1a\xe1e"
+		).unwrap();
+		assert_eq!(response.seeders(), Some(5));
+		assert_eq!(response.leechers(), Some(7));
+		
+		// No `incomplete`, so leechers are approximated from the peer list.
+		let response = BTrackerResponse::from_bytes(
+			b"d8:completei1e8:intervali1800e5:peers18:\x7f\x00\x00\x01\x1a\xe1\x7f\x00\x00\x02\x1a\xe1\x7f\x00\x00\x03\x1a\xe1e"
+		).unwrap();
+		assert_eq!(response.seeders(), Some(1));
+		assert_eq!(response.leechers(), Some(2));
+		
+		let response = BTrackerResponse::from_bytes(
+			b"d8:intervali1800e5:peers6:\x7f\x00\x00\x01\x1a\xe1e"
+		).unwrap();
+		assert_eq!(response.seeders(), None);
+		assert_eq!(response.leechers(), None);
+	}
+	
+	#[test]
+	fn test_failure_retry_in() {
+		let result = BTrackerResponse::from_announce_body(b"d14:failure reason12:unregistered8:retry ini30ee");
+		
+		match &result {
+			Err(AnnounceError::TrackerError(failure)) => {
+				assert_eq!(failure.reason, "unregistered");
+				assert_eq!(failure.retry_in, Some(30));
+			}
+			_ => panic!("expected a tracker error, got {:?}", result),
+		}
+		assert_eq!(result.unwrap_err().retry_in(), Some(30));
+		
+		let result = BTrackerResponse::from_announce_body(b"d14:failure reason12:unregistered8:retry in5:nevere");
+		assert_eq!(result.unwrap_err().retry_in(), None);
+		
+		let result = BTrackerResponse::from_announce_body(b"d14:failure reason12:unregisterede");
+		assert!(matches!(result, Err(AnnounceError::TrackerError(TrackerFailure { retry_in: None, .. }))));
+	}
+	
+	#[test]
+	fn test_peers_by_family() {
+		let response = BTrackerResponse::from_bytes(
+			b"d8:intervali1800e5:peers12:\x7f\x00\x00\x01\x1a\xe1\x0a\x00\x00\x02\x1a\xe26:peers618:\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x1a\xe1e"
+		).unwrap();
+		
+		let (v4, v6) = response.peers_by_family();
+		assert_eq!(v4.len(), 2);
+		assert_eq!(v6.len(), 1);
+		assert!(v4.iter().all(|p| p.ip.is_ipv4()));
+		assert_eq!(v6[0].ip, "::1".parse::<IpAddr>().unwrap());
+	}
+	
+	#[test]
+	fn test_is_empty_swarm() {
+		let response = BTrackerResponse::from_bytes(b"d8:intervali1800e5:peers0:e").unwrap();
+		assert!(response.is_empty_swarm());
+		
+		let response = BTrackerResponse::from_bytes(b"d8:completei0e10:incompletei0e8:intervali1800e5:peers0:e").unwrap();
+		assert!(response.is_empty_swarm());
+		
+		let response = BTrackerResponse::from_bytes(
+			b"d8:intervali1800e5:peers6:\x7f\x00\x00\x01\x1a\xe1e"
+		).unwrap();
+		assert!(!response.is_empty_swarm());
+		
+		// The tracker may not send any peers, despite the swarm having members.
+		let response = BTrackerResponse::from_bytes(b"d8:completei3e8:intervali1800e5:peers0:e").unwrap();
+		assert!(!response.is_empty_swarm());
+	}
+	
+	#[tokio::test]
+	async fn test_announce_compact() {
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+		use wiremock::matchers::{method, path, query_param};
+		
+		let body = b"d8:intervali1800e5:peers12:\x7f\x00\x00\x01\x1a\xe1\x0a\x00\x00\x02\x1a\xe2e";
+		
+		let server = MockServer::start().await;
+		Mock::given(method("GET"))
+			.and(path("/announce"))
+			.and(query_param("compact", "1"))
+			.respond_with(ResponseTemplate::new(200).set_body_bytes(&body[..]))
+			.mount(&server)
+			.await;
+		
+		let mut torrent = test_torrent();
+		torrent.announce = format!("{}/announce", server.uri());
+		
+		let ns = test_network_settings();
+		let client = ns.build_client().unwrap();
+		let response = announce(&client, &torrent, None, &ns).await.unwrap();
+		
+		assert!(response.compact);
+		assert_eq!(response.peers.len(), 2);
+		assert_eq!(response.peers[1].ip, "10.0.0.2".parse::<IpAddr>().unwrap());
+		assert_eq!(response.peers[1].port, 6882);
+		
+		// Trackers that ignore `compact=1` are still understood.
+		let body = b"d8:intervali1800e5:peersld2:ip9:127.0.0.17:peer id20:aaaaaaaaaaaaaaaaaaaa4:porti6881eeee";
+		let response = BTrackerResponse::from_bytes(body).unwrap();
+		assert!(!response.compact);
+		assert_eq!(response.peers.len(), 1);
+	}
+	
+	#[tokio::test]
+	async fn test_announce_compact_fallback() {
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+		use wiremock::matchers::{method, path, query_param};
+		
+		let body = b"d8:intervali1800e5:peersld2:ip9:127.0.0.17:peer id20:aaaaaaaaaaaaaaaaaaaa4:porti6881eeee";
+		
+		let server = MockServer::start().await;
+		Mock::given(method("GET"))
+			.and(path("/announce"))
+			.and(query_param("compact", "1"))
+			.respond_with(ResponseTemplate::new(400).set_body_string("compact not supported"))
+			.mount(&server)
+			.await;
+		Mock::given(method("GET"))
+			.and(path("/announce"))
+			.and(query_param("compact", "0"))
+			.respond_with(ResponseTemplate::new(200).set_body_bytes(&body[..]))
+			.mount(&server)
+			.await;
+		
+		let mut torrent = test_torrent();
+		torrent.announce = format!("{}/announce", server.uri());
+		
+		let ns = test_network_settings();
+		let client = ns.build_client().unwrap();
+		let response = announce(&client, &torrent, None, &ns).await.unwrap();
+		
+		assert!(!response.compact);
+		assert_eq!(response.peers.len(), 1);
+		
+		// Only the one retry, and `compact` was replaced rather than repeated.
+		let requests = server.received_requests().await.unwrap();
+		assert_eq!(requests.len(), 2);
+		assert_eq!(requests[1].url.query_pairs().filter(|(k, _)| k == "compact").count(), 1);
+		
+		// Other failures aren't retried.
+		server.reset().await;
+		Mock::given(method("GET"))
+			.respond_with(ResponseTemplate::new(200).set_body_bytes(&b"d14:failure reason22:torrent not registerede"[..]))
+			.mount(&server)
+			.await;
+		
+		assert!(matches!(announce(&client, &torrent, None, &ns).await, Err(AnnounceError::TrackerError(_))));
+		assert_eq!(server.received_requests().await.unwrap().len(), 1);
+		
+		// Nor is a 400 that has nothing to do with `compact`.
+		server.reset().await;
+		Mock::given(method("GET"))
+			.respond_with(ResponseTemplate::new(400).set_body_string("invalid passkey"))
+			.mount(&server)
+			.await;
+		
+		assert!(announce(&client, &torrent, None, &ns).await.is_err());
+		assert_eq!(server.received_requests().await.unwrap().len(), 1);
+	}
+	
+	#[test]
+	fn test_peer_dedup() {
+		use std::collections::HashSet;
+		
+		let peer = |ip: &str, peer_id: &str, port| BPeer {
+			ip: ip.parse().unwrap(),
+			peer_id: String::from(peer_id),
+			port,
+		};
+		
+		let peers: HashSet<BPeer> = vec![
+			peer("127.0.0.1", "", 6881),
+			peer("127.0.0.1", "aaaaaaaaaaaaaaaaaaaa", 6881),
+			peer("127.0.0.1", "", 6882),
+			peer("::1", "", 6881),
+		].into_iter().collect();
+		
+		assert_eq!(peers.len(), 3);
+		assert!(peers.contains(&peer("127.0.0.1", "bbbbbbbbbbbbbbbbbbbb", 6881)));
+	}
+	
+	#[test]
+	fn test_peer_connect_string() {
+		let peer = |ip: &str, port| BPeer {
+			ip: ip.parse().unwrap(),
+			peer_id: String::new(),
+			port,
+		};
+		
+		assert_eq!(peer("127.0.0.1", 6881).connect_string(), "127.0.0.1:6881");
+		assert_eq!(peer("::1", 6881).connect_string(), "[::1]:6881");
+		assert_eq!(peer("2001:db8::1", 51413).connect_string(), "[2001:db8::1]:51413");
+		
+		// IPv4-mapped addresses are kept as IPv6.
+		assert_eq!(peer("::ffff:10.0.0.1", 80).connect_string(), "[::ffff:10.0.0.1]:80");
+	}
+	
+	#[test]
+	fn test_announce_request_ip() {
+		let client  = Client::new();
+		let torrent = test_torrent();
+		let mut ns  = test_network_settings();
+		
+		ns.ip = Some("peer.example.com".parse().unwrap());
+		let request = announce_request(&client, &torrent, None, &ns).build().unwrap();
+		assert!(request.url().as_str().contains("&ip=peer.example.com"));
+		
+		ns.ip = Some("2001:db8::1".parse().unwrap());
+		let request = announce_request(&client, &torrent, None, &ns).build().unwrap();
+		assert!(request.url().as_str().contains("&ip=2001%3Adb8%3A%3A1"));
+	}
+	
+	#[tokio::test]
+	async fn test_announce_dry_run() {
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+		
+		let server = MockServer::start().await;
+		Mock::given(wiremock::matchers::any())
+			.respond_with(ResponseTemplate::new(200).set_body_bytes(&b"d8:intervali1800e5:peers0:e"[..]))
+			.expect(0)
+			.mount(&server)
+			.await;
+		
+		let mut torrent = test_torrent();
+		torrent.announce = format!("{}/announce?passkey=abc", server.uri());
+		
+		let url = announce_dry_run(&Client::new(), &torrent, Some(BAnnounceEvent::Started), &test_network_settings()).unwrap();
+		assert!(url.starts_with(&format!("{}/announce?passkey=abc&info_hash={}&peer_id={}&", server.uri(), torrent.encoded_info_hash, torrent.encoded_peer_id)));
+		assert!(url.ends_with("&event=started"));
+		assert!(server.received_requests().await.unwrap().is_empty());
+	}
+	
+	#[test]
+	fn test_announce_request_port() {
+		let client  = Client::new();
+		let torrent = test_torrent();
+		let mut ns  = test_network_settings();
+		
+		let pairs = query_pairs(announce_request(&client, &torrent, None, &ns));
+		assert!(pairs.contains(&(String::from("port"), String::from("6000"))));
+		
+		ns.announce_port = Some(51413);
+		let pairs = query_pairs(announce_request(&client, &torrent, None, &ns));
+		assert!(pairs.contains(&(String::from("port"), String::from("51413"))));
+		assert!(!pairs.contains(&(String::from("port"), String::from("6000"))));
+	}
+	
+	#[test]
+	fn test_announce_request_crypto() {
+		let client  = Client::new();
+		let torrent = test_torrent();
+		let mut ns  = test_network_settings();
+		
+		let pairs = query_pairs(announce_request(&client, &torrent, None, &ns));
+		assert!(!pairs.iter().any(|(k, _)| k == "supportcrypto" || k == "requirecrypto"));
+		
+		ns.support_crypto = true;
+		let pairs = query_pairs(announce_request(&client, &torrent, None, &ns));
+		assert!(pairs.contains(&(String::from("supportcrypto"), String::from("1"))));
+		assert!(!pairs.iter().any(|(k, _)| k == "requirecrypto"));
+		
+		ns.require_crypto = true;
+		let pairs = query_pairs(announce_request(&client, &torrent, None, &ns));
+		assert!(pairs.contains(&(String::from("supportcrypto"), String::from("1"))));
+		assert!(pairs.contains(&(String::from("requirecrypto"), String::from("1"))));
+	}
 }
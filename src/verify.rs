@@ -0,0 +1,294 @@
+use std::path::{Path, PathBuf};
+
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, BufReader};
+
+use crate::bitfield::PieceBitfield;
+use crate::hash::Sha1Hasher;
+use crate::metainfo::BInfo;
+
+
+// How much of a file `verify_against_path` reads at once. Reading a piece at a time means
+// many small reads when pieces are small, which is slower, especially on spinning disks.
+pub const DEFAULT_READ_BUFFER_SIZE: usize = 1024 * 1024;
+
+// Pieces are hashed as they're read, this much at a time, rather than read whole and then
+// hashed. Pieces of big torrents are often 16 MiB, which would otherwise all be in memory.
+const HASH_CHUNK_SIZE: usize = 64 * 1024;
+
+// Rechecks a torrent's content on disk against its piece hashes.
+// 
+// Files are expected at `root/name` for single-file torrents, and at `root/name/path...`
+// for multi-file torrents. They are read in torrent order, with each piece hashed as soon as
+// it's complete. Missing or short files are treated as zero-filled, so their pieces fail.
+pub async fn verify_against_path(info: &BInfo, root: &Path) -> Result<PieceBitfield, String> {
+	verify_against_path_with_buffer(info, root, DEFAULT_READ_BUFFER_SIZE).await
+}
+
+// Like `verify_against_path`, but reading files `read_buffer_size` bytes at a time. This
+// only changes how files are read; pieces are still hashed one at a time.
+pub async fn verify_against_path_with_buffer(info: &BInfo, root: &Path, read_buffer_size: usize) -> Result<PieceBitfield, String> {
+	if info.piece_length == 0 {
+		return Err(String::from("piece length is zero"));
+	}
+	
+	let piece_length = info.piece_length as usize;
+	let piece_hashes = info.piece_hashes().map_err(|e| e.to_string())?;
+	
+	let mut bitfield = PieceBitfield::new(piece_hashes.len());
+	let mut hasher   = Sha1Hasher::new();
+	let mut chunk    = vec![0; piece_length.min(HASH_CHUNK_SIZE)];
+	let mut filled   = 0;
+	let mut index    = 0;
+	
+	for (path, length) in file_layout(info, root)? {
+		let mut file = File::open(&path).await.ok()
+			.map(|f| BufReader::with_capacity(read_buffer_size, f));
+		let mut remaining = length;
+		
+		while remaining > 0 {
+			let want = (chunk.len().min(piece_length - filled) as u64).min(remaining) as usize;
+			let buf  = &mut chunk[..want];
+			buf.fill(0);
+			
+			// Once the file runs out (or can't be read) the rest of it is left zero-filled.
+			if let Some(f) = &mut file {
+				if !read_fully(f, buf).await {
+					file = None;
+				}
+			}
+			
+			hasher.update(buf);
+			filled    += want;
+			remaining -= want as u64;
+			
+			if filled == piece_length {
+				check_piece(std::mem::take(&mut hasher).finish(), index, &piece_hashes, &mut bitfield);
+				index += 1;
+				filled = 0;
+			}
+		}
+	}
+	
+	// The final piece may be shorter than `piece_length`.
+	if filled > 0 {
+		check_piece(hasher.finish(), index, &piece_hashes, &mut bitfield);
+	}
+	
+	Ok(bitfield)
+}
+
+// Rechecks the content of a single-file torrent, read from `path` rather than `root/name`.
+// Unlike `verify_against_path`, a missing file is an error. Pieces past the end of a file
+// shorter than the torrent's `length` fail.
+pub async fn verify_single_file(info: &BInfo, path: &Path) -> Result<PieceBitfield, String> {
+	let length = info.length
+		.ok_or_else(|| String::from("torrent is not a single-file torrent"))?;
+	
+	if info.piece_length == 0 {
+		return Err(String::from("piece length is zero"));
+	}
+	
+	let piece_hashes = info.piece_hashes().map_err(|e| e.to_string())?;
+	let mut bitfield = PieceBitfield::new(piece_hashes.len());
+	let mut file     = File::open(path).await.map_err(|e| e.to_string())?;
+	let mut chunk    = vec![0; (info.piece_length as usize).min(HASH_CHUNK_SIZE)];
+	let mut offset   = 0;
+	
+	'pieces: for index in 0..piece_hashes.len() {
+		let size = info.piece_length.min(length.saturating_sub(offset));
+		
+		if size == 0 {
+			break;
+		}
+		
+		let mut hasher = Sha1Hasher::new();
+		let mut hashed = 0;
+		
+		while hashed < size {
+			let want = (chunk.len() as u64).min(size - hashed) as usize;
+			
+			if !read_fully(&mut file, &mut chunk[..want]).await {
+				break 'pieces;
+			}
+			
+			hasher.update(&chunk[..want]);
+			hashed += want as u64;
+		}
+		
+		check_piece(hasher.finish(), index, &piece_hashes, &mut bitfield);
+		offset += size;
+	}
+	
+	Ok(bitfield)
+}
+
+fn file_layout(info: &BInfo, root: &Path) -> Result<Vec<(PathBuf, u64)>, String> {
+	let paths = info.relative_paths()?;
+	
+	Ok(paths.iter().map(|p| root.join(p)).zip(info.file_lengths()).collect())
+}
+
+// Fills `buf` from `file`, returning false if EOF or an error was hit first.
+async fn read_fully<R: AsyncRead + Unpin>(file: &mut R, buf: &mut [u8]) -> bool {
+	let mut filled = 0;
+	
+	while filled < buf.len() {
+		match file.read(&mut buf[filled..]).await {
+			Ok(0) | Err(_) => return false,
+			Ok(n) => filled += n,
+		}
+	}
+	
+	true
+}
+
+fn check_piece(actual: [u8; 20], index: usize, piece_hashes: &[&[u8]], bitfield: &mut PieceBitfield) {
+	if let Some(expected) = piece_hashes.get(index) {
+		if actual.as_ref() == *expected {
+			bitfield.set(index, true);
+		}
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	use crate::hash::sha1;
+	use crate::metainfo::BMetainfoBuilder;
+	
+	fn hash_pieces(content: &[u8], piece_length: usize) -> Vec<u8> {
+		content.chunks(piece_length)
+			.flat_map(|p| sha1(p).as_ref().to_vec())
+			.collect()
+	}
+	
+	fn test_dir(name: &str) -> PathBuf {
+		let dir = std::env::temp_dir().join(format!("acorntorrent-{}-{}", name, std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(&dir).unwrap();
+		dir
+	}
+	
+	#[tokio::test]
+	async fn test_verify_complete() {
+		let content: Vec<u8> = (0..40000u32).map(|i| (i * 7 + 3) as u8).collect();
+		let (a, b) = content.split_at(25000);
+		
+		let metainfo = BMetainfoBuilder::new()
+			.announce("http://tracker.example.com/announce")
+			.name("multi")
+			.piece_length(16384)
+			.pieces(hash_pieces(&content, 16384))
+			.add_file(a.len() as u64, vec![String::from("a.bin")])
+			.add_file(b.len() as u64, vec![String::from("sub"), String::from("b.bin")])
+			.build()
+			.unwrap();
+		
+		let root = test_dir("verify-complete");
+		std::fs::create_dir_all(root.join("multi/sub")).unwrap();
+		std::fs::write(root.join("multi/a.bin"), a).unwrap();
+		std::fs::write(root.join("multi/sub/b.bin"), b).unwrap();
+		
+		let bitfield = verify_against_path(&metainfo.info, &root).await.unwrap();
+		
+		assert_eq!(bitfield.len(), 3);
+		assert!(bitfield.is_complete());
+		
+		// With the second file missing, only the first piece lies entirely within the first file.
+		std::fs::remove_file(root.join("multi/sub/b.bin")).unwrap();
+		let bitfield = verify_against_path(&metainfo.info, &root).await.unwrap();
+		
+		assert!(bitfield.get(0));
+		assert!(!bitfield.get(1));
+		assert!(!bitfield.get(2));
+		
+		std::fs::remove_dir_all(&root).unwrap();
+	}
+	
+	#[tokio::test]
+	async fn test_verify_read_buffer_size() {
+		// Small pieces, so that a piece at a time is many reads, with one piece corrupted.
+		let mut content: Vec<u8> = (0..1_000_000u32).map(|i| (i * 7 + 3) as u8).collect();
+		let pieces = hash_pieces(&content, 1024);
+		content[500_000] ^= 0xff;
+		
+		let metainfo = BMetainfoBuilder::new()
+			.announce("http://tracker.example.com/announce")
+			.name("large.bin")
+			.piece_length(1024)
+			.pieces(pieces)
+			.length(content.len() as u64)
+			.build()
+			.unwrap();
+		
+		let root = test_dir("verify-buffer");
+		std::fs::write(root.join("large.bin"), &content).unwrap();
+		
+		let unbuffered = verify_against_path_with_buffer(&metainfo.info, &root, 0).await.unwrap();
+		assert_eq!(unbuffered.count_ones(), unbuffered.len() - 1);
+		assert!(!unbuffered.get(500_000 / 1024));
+		
+		// Buffers smaller than, unaligned with, and larger than the pieces and file.
+		for size in [1, 1000, 4096, DEFAULT_READ_BUFFER_SIZE, 2 * DEFAULT_READ_BUFFER_SIZE] {
+			let bitfield = verify_against_path_with_buffer(&metainfo.info, &root, size).await.unwrap();
+			assert_eq!(bitfield.as_bytes(), unbuffered.as_bytes(), "read buffer size {}", size);
+		}
+		
+		std::fs::remove_dir_all(&root).unwrap();
+	}
+	
+	#[tokio::test]
+	async fn test_verify_single_file() {
+		let content: Vec<u8> = (0..40000u32).map(|i| (i * 7 + 3) as u8).collect();
+		
+		let metainfo = BMetainfoBuilder::new()
+			.announce("http://tracker.example.com/announce")
+			.name("single.bin")
+			.piece_length(16384)
+			.pieces(hash_pieces(&content, 16384))
+			.length(content.len() as u64)
+			.build()
+			.unwrap();
+		
+		// Saved under a different name to the torrent's.
+		let root = test_dir("verify-single");
+		let path = root.join("renamed.bin");
+		std::fs::write(&path, &content).unwrap();
+		
+		let bitfield = verify_single_file(&metainfo.info, &path).await.unwrap();
+		assert_eq!(bitfield.len(), 3);
+		assert!(bitfield.is_complete());
+		
+		// Cut short in the middle of the second piece.
+		std::fs::write(&path, &content[..20000]).unwrap();
+		let bitfield = verify_single_file(&metainfo.info, &path).await.unwrap();
+		
+		assert!(bitfield.get(0));
+		assert!(!bitfield.get(1));
+		assert!(!bitfield.get(2));
+		
+		assert!(verify_single_file(&metainfo.info, &root.join("missing.bin")).await.is_err());
+		
+		// The same check, as a method.
+		std::fs::write(&path, &content).unwrap();
+		assert!(metainfo.info.verify_single_file(&path).await.unwrap().is_complete());
+		
+		std::fs::remove_dir_all(&root).unwrap();
+	}
+	
+	#[test]
+	fn test_file_layout_v2_only() {
+		use crate::metainfo::BMetainfo;
+		
+		let metainfo = BMetainfo::from_bytes(b"d8:announce17:http://a/announce4:infod9:file treed5:a.bind0:d6:lengthi5e11:pieces root32:aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaee5:b.bind0:d6:lengthi7e11:pieces root32:aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaeee12:meta versioni2e4:name3:dir12:piece lengthi16384eee").unwrap();
+		let root = Path::new("root");
+		
+		assert_eq!(file_layout(&metainfo.info, root).unwrap(), vec![
+			(root.join("dir").join("a.bin"), 5),
+			(root.join("dir").join("b.bin"), 7),
+		]);
+	}
+}